@@ -0,0 +1,139 @@
+//! Where the art and sounds come from: compiled into the game, or with
+//! `--dev-assets`, read from the source tree and watched for changes so they
+//! can be swapped in without restarting.
+
+use std::borrow::Cow;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often the asset files are checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Every asset, by its path relative to the source tree.
+const EMBEDDED: &[(&str, &[u8])] = &[
+    ("tiles.svg", include_bytes!("../tiles.svg")),
+    ("src/effects.txt", include_bytes!("effects.txt")),
+    ("src/textures/0.png", include_bytes!("textures/0.png")),
+    ("src/textures/1.png", include_bytes!("textures/1.png")),
+    ("src/textures/2.png", include_bytes!("textures/2.png")),
+    ("src/textures/3.png", include_bytes!("textures/3.png")),
+    ("src/textures/4.png", include_bytes!("textures/4.png")),
+    ("src/textures/5.png", include_bytes!("textures/5.png")),
+    ("src/textures/6.png", include_bytes!("textures/6.png")),
+    ("src/textures/7.png", include_bytes!("textures/7.png")),
+    ("src/textures/8.png", include_bytes!("textures/8.png")),
+    ("src/textures/mine.png", include_bytes!("textures/mine.png")),
+    ("src/textures/unrevealed.png", include_bytes!("textures/unrevealed.png")),
+    ("src/textures/hover.png", include_bytes!("textures/hover.png")),
+    ("src/textures/flag.png", include_bytes!("textures/flag.png")),
+    ("src/textures/hover_flag.png", include_bytes!("textures/hover_flag.png")),
+    ("src/sfx/dig.ogg", include_bytes!("sfx/dig.ogg")),
+    ("src/sfx/boom.ogg", include_bytes!("sfx/boom.ogg")),
+    ("src/sfx/flag_1.wav", include_bytes!("sfx/flag_1.wav")),
+    ("src/sfx/flag_2.wav", include_bytes!("sfx/flag_2.wav")),
+    ("src/sfx/unflag.wav", include_bytes!("sfx/unflag.wav")),
+    ("src/sfx/chord.wav", include_bytes!("sfx/chord.wav")),
+    ("src/sfx/wasted.wav", include_bytes!("sfx/wasted.wav")),
+    ("src/sfx/win.wav", include_bytes!("sfx/win.wav")),
+    ("src/sfx/best_time.wav", include_bytes!("sfx/best_time.wav")),
+    ("src/sfx/hover.wav", include_bytes!("sfx/hover.wav")),
+    ("src/sfx/presets.txt", include_bytes!("sfx/presets.txt")),
+];
+
+pub struct Assets {
+    /// The source tree, when loading from the filesystem.
+    dir: Option<PathBuf>,
+    /// Last seen modification time of each asset, in `EMBEDDED` order.
+    modified: Vec<Option<SystemTime>>,
+    last_poll: Instant,
+}
+
+impl Assets {
+    /// Only the assets compiled into the game.
+    pub fn embedded() -> Self {
+        Self {
+            dir: None,
+            modified: Vec::new(),
+            last_poll: Instant::now(),
+        }
+    }
+
+    /// Reads the assets from the source tree the game was built from.
+    pub fn from_source_tree() -> Self {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let modified = EMBEDDED
+            .iter()
+            .map(|(name, _)| modified(&dir.join(name)))
+            .collect();
+
+        Self {
+            dir: Some(dir),
+            modified,
+            last_poll: Instant::now(),
+        }
+    }
+
+    /// The current contents of an asset, falling back to the embedded copy
+    /// if the file can't be read.
+    pub fn read(&self, name: &str) -> Cow<'static, [u8]> {
+        if let Some(dir) = &self.dir {
+            let path = dir.join(name);
+            match std::fs::read(&path) {
+                Ok(data) => return Cow::Owned(data),
+                Err(e) => eprintln!("can't read {}, using the embedded copy: {}", path.display(), e),
+            }
+        }
+
+        Cow::Borrowed(embedded(name))
+    }
+
+    /// Reads and parses an asset, falling back to the embedded copy if
+    /// either fails.
+    pub fn load<T, F: Fn(&[u8]) -> Result<T, String>>(&self, name: &str, parse: F) -> T {
+        self.try_load(name, parse).expect("embedded assets are valid")
+    }
+
+    /// Like `load`, but fails if the embedded copy can't be parsed either.
+    pub fn try_load<T, F: Fn(&[u8]) -> Result<T, String>>(&self, name: &str, parse: F) -> Result<T, String> {
+        if self.dir.is_some() {
+            match parse(&self.read(name)) {
+                Ok(asset) => return Ok(asset),
+                Err(e) => eprintln!("can't load {}, using the embedded copy: {}", name, e),
+            }
+        }
+
+        parse(embedded(name))
+    }
+
+    /// Names of the assets whose files changed since the last call.
+    pub fn changed(&mut self) -> Vec<&'static str> {
+        let dir = match &self.dir {
+            Some(dir) if self.last_poll.elapsed() >= POLL_INTERVAL => dir,
+            _ => return Vec::new(),
+        };
+        self.last_poll = Instant::now();
+
+        let mut changed = Vec::new();
+        for ((name, _), last_modified) in EMBEDDED.iter().zip(&mut self.modified) {
+            let modified = modified(&dir.join(name));
+            if modified != *last_modified {
+                *last_modified = modified;
+                changed.push(*name);
+            }
+        }
+
+        changed
+    }
+}
+
+fn embedded(name: &str) -> &'static [u8] {
+    EMBEDDED
+        .iter()
+        .find(|(embedded_name, _)| *embedded_name == name)
+        .map(|(_, data)| *data)
+        .unwrap_or_else(|| panic!("no asset named {}", name))
+}
+
+fn modified(path: &std::path::Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
@@ -1,39 +1,355 @@
-use sdl2::{audio::AudioDevice, AudioSubsystem};
+use crate::music::Stream;
+use crate::ring::{self, Consumer, Producer};
+use crate::sound::Sound;
+use sdl2::{audio::{AudioDevice, AudioSpec}, Sdl};
+use std::cell::RefCell;
+use std::collections::VecDeque;
 
+/// The rate asked for; devices may pick another, and sounds are resampled
+/// to whatever they play at.
 pub const SAMPLE_RATE: u32 = 44100;
-const CHANNEL_COUNT: u8 = 1;
+/// Sounds themselves are mono, and spread over the channels when mixed.
+const CHANNEL_COUNT: u8 = 2;
 const AUDIO_BUFFER_SIZE: u16 = 512;
 
+/// Most sounds that play at once; more steal the oldest voice.
+const MAX_VOICES: usize = 32;
+/// Most music streams that play at once, while crossfading.
+const MAX_STREAMS: usize = 4;
+/// How many commands can wait for the audio callback; more are dropped.
+const MAX_COMMANDS: usize = 256;
+/// How many finished voices and streams can wait to be freed by the game. More, if the
+/// game stops collecting them, are freed by the audio callback after all.
+const MAX_FINISHED: usize = 256;
+/// How many of the latest sounds the null sink remembers.
+const MAX_RECORDED: usize = 64;
+/// Level above which the mix is gradually compressed instead of clipping.
+const SOFT_CLIP_THRESHOLD: f32 = 0.8;
+/// Roughly how long volume changes take, in seconds, so they don't click.
+const VOLUME_SMOOTHING: f32 = 0.05;
+
+/// What a sound is, for which volume applies to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    Effects,
+    Music,
+}
+
+const CATEGORY_COUNT: usize = 2;
+
+/// Volume levels from 0 to 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Volume {
+    pub master: f32,
+    pub effects: f32,
+    pub music: f32,
+    pub muted: bool,
+}
+
+impl Default for Volume {
+    fn default() -> Self {
+        Self {
+            master: 1.0,
+            effects: 1.0,
+            music: 0.6,
+            muted: false,
+        }
+    }
+}
+
+impl Volume {
+    /// The gain every sound of each category gets.
+    fn gains(&self) -> [f32; CATEGORY_COUNT] {
+        if self.muted {
+            return [0.0; CATEGORY_COUNT];
+        }
+
+        let mut gains = [0.0; CATEGORY_COUNT];
+        gains[Category::Effects as usize] = self.master * self.effects;
+        gains[Category::Music as usize] = self.master * self.music;
+        gains
+    }
+}
+
+/// A sound playing, or about to.
+#[derive(Debug)]
+pub struct Voice {
+    sound: Sound,
+    /// How far into `sound` playback is, in samples.
+    position: f64,
+    gain: f32,
+    /// Playback speed; above 1 sounds higher.
+    pitch: f32,
+    /// -1 is fully left, 1 fully right.
+    pan: f32,
+    category: Category,
+}
+
+impl Voice {
+    pub fn new(sound: &Sound) -> Self {
+        Self {
+            sound: sound.clone(),
+            position: 0.0,
+            gain: 1.0,
+            pitch: 1.0,
+            pan: 0.0,
+            category: Category::Effects,
+        }
+    }
+
+    pub fn with_gain(mut self, gain: f32) -> Self {
+        self.gain = gain.max(0.0);
+        self
+    }
+
+    pub fn with_pitch(mut self, pitch: f32) -> Self {
+        self.pitch = pitch.max(0.01);
+        self
+    }
+
+    pub fn with_pan(mut self, pan: f32) -> Self {
+        self.pan = pan.clamp(-1.0, 1.0);
+        self
+    }
+
+    pub fn with_category(mut self, category: Category) -> Self {
+        self.category = category;
+        self
+    }
+
+    pub fn sound(&self) -> &Sound {
+        &self.sound
+    }
+
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+
+    pub fn pitch(&self) -> f32 {
+        self.pitch
+    }
+
+    pub fn pan(&self) -> f32 {
+        self.pan
+    }
+
+    pub fn category(&self) -> Category {
+        self.category
+    }
+
+    fn is_done(&self) -> bool {
+        self.position >= self.sound.len() as f64
+    }
+
+    /// Gain of each output channel, keeping the power constant across pans.
+    fn channel_gains(&self, channels: usize) -> [f32; 2] {
+        if channels < 2 {
+            return [self.gain; 2];
+        }
+
+        let angle = (self.pan + 1.0) * std::f32::consts::FRAC_PI_4;
+        [self.gain * angle.cos(), self.gain * angle.sin()]
+    }
+}
+
+#[derive(Debug)]
+enum Command {
+    Play(Voice),
+    /// Fades out the music playing, and this in.
+    PlayMusic(Stream),
+    SetVolume(Volume),
+}
+
+/// What the audio callback is done with. It goes back to the game to be
+/// dropped, since freeing memory on the audio thread can take a lock.
+// only ever held on to until it's dropped
+#[allow(dead_code)]
+#[derive(Debug)]
+enum Finished {
+    Voice(Voice),
+    Stream(Stream),
+}
+
+#[derive(Debug)]
+enum Sink {
+    /// Commands go through a queue to the audio callback, so playing a
+    /// sound never waits on the audio thread, and what it's done with comes
+    /// back through another.
+    Device {
+        commands: Producer<Command>,
+        finished: Consumer<Finished>,
+    },
+    /// Nothing is heard, but the latest sounds are kept so tests can check
+    /// what would have played.
+    Null(RefCell<VecDeque<Voice>>),
+}
+
+/// The game's side of the audio callback.
+#[derive(Debug)]
+pub struct Mixer {
+    sink: Sink,
+    sample_rate: u32,
+}
+
+impl Mixer {
+    /// A mixer that doesn't play anything.
+    pub fn null() -> Self {
+        Self {
+            sink: Sink::Null(RefCell::new(VecDeque::new())),
+            sample_rate: SAMPLE_RATE,
+        }
+    }
+
+    /// Whether nothing played is heard, so there's no point preparing it.
+    pub fn is_null(&self) -> bool {
+        matches!(self.sink, Sink::Null(_))
+    }
+
+    /// What sounds have to be resampled to.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn play(&self, voice: Voice) {
+        match &self.sink {
+            Sink::Device { commands, .. } => {
+                // fails only when the callback has fallen far behind, when
+                // one sound more or less isn't heard anyway
+                let _ = commands.push(Command::Play(voice));
+            }
+            Sink::Null(recorded) => {
+                let mut recorded = recorded.borrow_mut();
+                if recorded.len() >= MAX_RECORDED {
+                    recorded.pop_front();
+                }
+                recorded.push_back(voice);
+            }
+        }
+    }
+
+    /// Crossfades to a music stream. The null mixer drops it, which stops
+    /// its decoding, but `Music` doesn't start any for it.
+    pub fn play_music(&self, stream: Stream) {
+        if let Sink::Device { commands, .. } = &self.sink {
+            let _ = commands.push(Command::PlayMusic(stream));
+        }
+    }
+
+    pub fn set_volume(&self, volume: Volume) {
+        if let Sink::Device { commands, .. } = &self.sink {
+            let _ = commands.push(Command::SetVolume(volume));
+        }
+    }
+
+    /// Drops what the audio callback is done with. Called every frame.
+    pub fn free_finished(&self) {
+        if let Sink::Device { finished, .. } = &self.sink {
+            while finished.pop().is_some() {}
+        }
+    }
+
+    /// Takes the sounds a null mixer would have played, oldest first.
+    pub fn take_recorded(&self) -> Vec<Voice> {
+        match &self.sink {
+            Sink::Device { .. } => Vec::new(),
+            Sink::Null(recorded) => recorded.borrow_mut().drain(..).collect(),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct AudioCallback {
-    //sound_effects: Vec<&'static [i16]>,
-    sound_effect: &'static [i16],
+    commands: Consumer<Command>,
+    finished: Producer<Finished>,
+    /// Allocated up front; the callback shouldn't allocate.
+    voices: Vec<Voice>,
+    music: Vec<Stream>,
+    /// The mix before it's clipped into the output.
+    mix: Vec<f32>,
+    channels: usize,
+    sample_rate: u32,
+    /// Per category, what the volume is heading towards and where it's at.
+    target_gains: [f32; CATEGORY_COUNT],
+    gains: [f32; CATEGORY_COUNT],
 }
 
 impl AudioCallback {
-    pub fn new_device(audio: &AudioSubsystem) -> AudioDevice<AudioCallback> {
-        audio
-            .open_playback(
-                None,
-                &sdl2::audio::AudioSpecDesired {
-                    freq: Some(SAMPLE_RATE as i32),
-                    channels: Some(CHANNEL_COUNT),
-                    samples: Some(AUDIO_BUFFER_SIZE),
-                },
-                |_| AudioCallback::new(),
-            )
-            .unwrap()
-    }
-
-    fn new() -> Self {
+    /// Opens the default playback device. Without one, the game carries on
+    /// silently with a null mixer.
+    pub fn open(sdl: &Sdl, volume: Volume) -> (Option<AudioDevice<AudioCallback>>, Mixer) {
+        match AudioCallback::new_device(sdl, volume) {
+            Ok((device, mixer)) => (Some(device), mixer),
+            Err(e) => {
+                eprintln!("no audio, playing silently: {}", e);
+                (None, Mixer::null())
+            }
+        }
+    }
+
+    fn new_device(sdl: &Sdl, volume: Volume) -> Result<(AudioDevice<AudioCallback>, Mixer), String> {
+        let audio = sdl.audio()?;
+        let (commands, received) = ring::ring(MAX_COMMANDS);
+        let (retired, finished) = ring::ring(MAX_FINISHED);
+
+        let device = audio.open_playback(
+            None,
+            &sdl2::audio::AudioSpecDesired {
+                freq: Some(SAMPLE_RATE as i32),
+                channels: Some(CHANNEL_COUNT),
+                samples: Some(AUDIO_BUFFER_SIZE),
+            },
+            |spec| AudioCallback::new(received, retired, spec, volume),
+        )?;
+
+        let mixer = Mixer {
+            sink: Sink::Device { commands, finished },
+            sample_rate: device.spec().freq as u32,
+        };
+
+        Ok((device, mixer))
+    }
+
+    fn new(commands: Consumer<Command>, finished: Producer<Finished>, spec: AudioSpec, volume: Volume) -> Self {
+        let channels = spec.channels as usize;
+
         Self {
-            //sound_effect: Vec::new(),
-            sound_effect: &[],
+            commands,
+            finished,
+            voices: Vec::with_capacity(MAX_VOICES),
+            music: Vec::with_capacity(MAX_STREAMS),
+            mix: vec![0.0; spec.samples as usize * channels],
+            channels,
+            sample_rate: spec.freq as u32,
+            target_gains: volume.gains(),
+            gains: volume.gains(),
         }
     }
 
-    pub fn play_sound_effect(&mut self, sound_effect: &'static [i16]) {
-        self.sound_effect = sound_effect;
+    fn handle_commands(&mut self) {
+        while let Some(command) = self.commands.pop() {
+            match command {
+                Command::Play(voice) => {
+                    if self.voices.len() >= MAX_VOICES {
+                        // steal the voice that has played the longest
+                        let oldest = (0..self.voices.len())
+                            .max_by(|&a, &b| self.voices[a].position.total_cmp(&self.voices[b].position))
+                            .unwrap();
+                        let stolen = self.voices.swap_remove(oldest);
+                        retire(&self.finished, Finished::Voice(stolen));
+                    }
+                    self.voices.push(voice);
+                }
+                Command::PlayMusic(stream) => {
+                    if self.music.len() >= MAX_STREAMS {
+                        let oldest = self.music.remove(0);
+                        retire(&self.finished, Finished::Stream(oldest));
+                    }
+                    self.music.iter_mut().for_each(Stream::fade_out);
+                    self.music.push(stream);
+                }
+                Command::SetVolume(volume) => self.target_gains = volume.gains(),
+            }
+        }
     }
 }
 
@@ -41,12 +357,160 @@ impl sdl2::audio::AudioCallback for AudioCallback {
     type Channel = i16;
 
     fn callback(&mut self, samples: &mut [i16]) {
-        let end = samples.len().min(self.sound_effect.len());
+        self.handle_commands();
+
+        if self.mix.len() < samples.len() {
+            self.mix.resize(samples.len(), 0.0);
+        }
+        let mix = &mut self.mix[..samples.len()];
+        mix.iter_mut().for_each(|sample| *sample = 0.0);
+
+        let channels = self.channels;
+        let frames = mix.len() / channels;
 
-        samples[0..end].copy_from_slice(&self.sound_effect[0..end]);
-        self.sound_effect = &self.sound_effect[end..];
-        for sample in samples.iter_mut().skip(end) {
-            *sample = 0;
+        // volume changes ramp linearly over each buffer
+        let step = (frames as f32 / (VOLUME_SMOOTHING * self.sample_rate as f32)).min(1.0);
+        let start_gains = self.gains;
+        for (gain, target) in self.gains.iter_mut().zip(&self.target_gains) {
+            *gain += (target - *gain) * step;
         }
+
+        for voice in &mut self.voices {
+            let gains = voice.channel_gains(channels);
+            let start = start_gains[voice.category as usize];
+            let ramp = (self.gains[voice.category as usize] - start) / frames as f32;
+            let sound = &voice.sound;
+
+            // pitched voices step through the sound faster or slower,
+            // interpolating between its samples
+            let mut position = voice.position;
+            for (i, frame) in mix.chunks_mut(channels).enumerate() {
+                let index = position as usize;
+                if index >= sound.len() {
+                    break;
+                }
+
+                let next = sound.get(index + 1).copied().unwrap_or(0) as f32;
+                let t = (position - index as f64) as f32;
+                let sample = sound[index] as f32 * (1.0 - t) + next * t;
+
+                let sample = sample / i16::MAX as f32 * (start + ramp * i as f32);
+                for (channel, out) in frame.iter_mut().enumerate() {
+                    *out += sample * gains[channel.min(1)];
+                }
+
+                position += voice.pitch as f64;
+            }
+            voice.position = position;
+        }
+        let mut i = 0;
+        while i < self.voices.len() {
+            if self.voices[i].is_done() {
+                let voice = self.voices.swap_remove(i);
+                retire(&self.finished, Finished::Voice(voice));
+            } else {
+                i += 1;
+            }
+        }
+
+        let start = start_gains[Category::Music as usize];
+        let ramp = (self.gains[Category::Music as usize] - start) / frames as f32;
+        for stream in &mut self.music {
+            for (i, frame) in mix.chunks_mut(channels).enumerate() {
+                let [left, right] = stream.next_frame();
+                let gain = start + ramp * i as f32;
+                if channels < 2 {
+                    frame[0] += (left + right) / 2.0 * gain;
+                    continue;
+                }
+                for (channel, out) in frame.iter_mut().enumerate() {
+                    *out += [left, right][channel.min(1)] * gain;
+                }
+            }
+        }
+        // in order, since the oldest stream is the first to go
+        let mut i = 0;
+        while i < self.music.len() {
+            if self.music[i].is_done() {
+                let stream = self.music.remove(i);
+                retire(&self.finished, Finished::Stream(stream));
+            } else {
+                i += 1;
+            }
+        }
+
+        for (out, &sample) in samples.iter_mut().zip(mix.iter()) {
+            *out = (soft_clip(sample) * i16::MAX as f32) as i16;
+        }
+    }
+}
+
+/// Sends what the audio callback is done with back to the game to drop.
+fn retire(finished: &Producer<Finished>, done: Finished) {
+    // if the game has stopped collecting, it's dropped here after all
+    let _ = finished.push(done);
+}
+
+/// Passes quiet samples through, and bends louder ones so that they
+/// approach but never exceed full scale.
+fn soft_clip(sample: f32) -> f32 {
+    let level = sample.abs();
+    if level <= SOFT_CLIP_THRESHOLD {
+        return sample;
+    }
+
+    let headroom = 1.0 - SOFT_CLIP_THRESHOLD;
+    let compressed = SOFT_CLIP_THRESHOLD + headroom * ((level - SOFT_CLIP_THRESHOLD) / headroom).tanh();
+    compressed.copysign(sample)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sound(length: usize) -> Sound {
+        vec![0; length].into()
+    }
+
+    #[test]
+    fn null_mixer_records_what_plays() {
+        let mixer = Mixer::null();
+        let click = sound(10);
+        let music = sound(20);
+        mixer.play(Voice::new(&click).with_pan(-2.0));
+        mixer.play(Voice::new(&music).with_gain(0.5).with_category(Category::Music));
+
+        let recorded = mixer.take_recorded();
+        assert_eq!(recorded.len(), 2);
+        assert!(Sound::ptr_eq(recorded[0].sound(), &click));
+        assert_eq!(recorded[0].pan(), -1.0);
+        assert!(Sound::ptr_eq(recorded[1].sound(), &music));
+        assert_eq!(recorded[1].gain(), 0.5);
+        assert_eq!(recorded[1].category(), Category::Music);
+
+        assert!(mixer.take_recorded().is_empty());
+    }
+
+    #[test]
+    fn null_mixer_keeps_the_latest() {
+        let mixer = Mixer::null();
+        let sounds: Vec<_> = (1..=MAX_RECORDED + 5).map(sound).collect();
+        for sound in &sounds {
+            mixer.play(Voice::new(sound));
+        }
+
+        let recorded = mixer.take_recorded();
+        assert_eq!(recorded.len(), MAX_RECORDED);
+        assert_eq!(recorded[0].sound().len(), 6);
+        assert_eq!(recorded.last().unwrap().sound().len(), MAX_RECORDED + 5);
+    }
+
+    #[test]
+    fn soft_clips_loud_samples() {
+        assert_eq!(soft_clip(0.5), 0.5);
+        assert_eq!(soft_clip(-0.5), -0.5);
+        assert!(soft_clip(0.9) > SOFT_CLIP_THRESHOLD && soft_clip(0.9) < 0.9);
+        assert!(soft_clip(10.0) <= 1.0);
+        assert!(soft_clip(-10.0) >= -1.0);
     }
 }
@@ -0,0 +1,88 @@
+//! Something happening to cells one after another, spreading out from
+//! where it started.
+
+use rand::Rng;
+
+#[derive(Debug, Default)]
+pub struct Cascade {
+    /// Cells still to come, with how long until they do.
+    pending: Vec<(f32, (u16, u16))>,
+}
+
+impl Cascade {
+    /// Schedules `cells`, `step` seconds apart per cell of distance from
+    /// `origin`, plus up to `jitter` seconds so cells at the same distance
+    /// don't all come at once. The whole cascade is sped up to take at
+    /// most `max_time` seconds.
+    pub fn start(&mut self, origin: (u16, u16), cells: Vec<(u16, u16)>, step: f32, jitter: f32, max_time: f32) {
+        let distance = |(x, y): (u16, u16)| {
+            let dx = x as f32 - origin.0 as f32;
+            let dy = y as f32 - origin.1 as f32;
+            (dx * dx + dy * dy).sqrt()
+        };
+
+        let longest = cells.iter().map(|&pos| distance(pos) * step).fold(0.0, f32::max);
+        let speed_up = if longest > max_time { max_time / longest } else { 1.0 };
+
+        let mut rng = rand::thread_rng();
+        self.pending.extend(cells.into_iter().map(|pos| {
+            let jitter = if jitter > 0.0 { rng.gen_range(0.0, jitter) } else { 0.0 };
+            (distance(pos) * step * speed_up + jitter, pos)
+        }));
+    }
+
+    /// Advances time, returning the cells whose turn has come.
+    pub fn update(&mut self, delta: f32) -> Vec<(u16, u16)> {
+        let mut due = Vec::new();
+        self.pending.retain(|&(delay, pos)| {
+            if delay <= delta {
+                due.push(pos);
+                false
+            } else {
+                true
+            }
+        });
+        self.pending.iter_mut().for_each(|(delay, _)| *delay -= delta);
+
+        due
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delays_cells_by_their_distance() {
+        let mut cascade = Cascade::default();
+        cascade.start((0, 0), vec![(3, 4), (0, 0)], 0.1, 0.0, 10.0);
+
+        assert_eq!(cascade.update(0.0), vec![(0, 0)]);
+        assert!(cascade.update(0.4).is_empty());
+        assert_eq!(cascade.update(0.11), vec![(3, 4)]);
+        assert!(cascade.is_done());
+    }
+
+    #[test]
+    fn speeds_up_to_finish_in_time() {
+        let mut cascade = Cascade::default();
+        cascade.start((0, 0), vec![(10, 0), (5, 0)], 1.0, 0.0, 2.0);
+
+        assert!(cascade.update(0.9).is_empty());
+        assert_eq!(cascade.update(0.2), vec![(5, 0)]);
+        assert_eq!(cascade.update(1.0), vec![(10, 0)]);
+        assert!(cascade.is_done());
+    }
+
+    #[test]
+    fn jitters_within_the_given_time() {
+        let mut cascade = Cascade::default();
+        cascade.start((0, 0), vec![(0, 0); 20], 0.1, 0.5, 10.0);
+
+        assert_eq!(cascade.update(0.5).len(), 20);
+    }
+}
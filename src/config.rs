@@ -0,0 +1,173 @@
+//! Plain text files of `key = value` lines, used for theme manifests, the
+//! saved settings and the sound and particle effects.
+//!
+//! A `#` starts a comment at the start of a line, or standing on its own
+//! after a value, so colors like `#16233c` can still be values:
+//!
+//! ```text
+//! # a comment
+//! background = #16233c # another
+//! ```
+
+use std::path::PathBuf;
+
+/// A key and its value, or why a line isn't one.
+pub type Entry<'a> = Result<(&'a str, &'a str), String>;
+
+/// The entries of `text` with their line numbers.
+pub fn parse(text: &str) -> Vec<(usize, Entry<'_>)> {
+    text.lines()
+        .enumerate()
+        .filter_map(|(i, line)| {
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                return None;
+            }
+
+            let mut parts = line.splitn(2, '=');
+            let key = parts.next().unwrap().trim();
+            let entry = match parts.next() {
+                Some(value) => Ok((key, value.trim())),
+                None => Err("expected `key = value`".to_string()),
+            };
+
+            Some((i + 1, entry))
+        })
+        .collect()
+}
+
+/// Cuts the comment off `line`, if it has one.
+fn strip_comment(line: &str) -> &str {
+    if line.trim_start().starts_with('#') {
+        return "";
+    }
+
+    let bytes = line.as_bytes();
+    let starts_comment = |i: usize| {
+        bytes[i] == b'#'
+            && bytes[i - 1].is_ascii_whitespace()
+            && bytes.get(i + 1).map(|b| b.is_ascii_whitespace()).unwrap_or(true)
+    };
+    match (1..bytes.len()).find(|&i| starts_comment(i)) {
+        Some(i) => &line[..i],
+        None => line,
+    }
+}
+
+/// Parses a file describing several named things, with keys like
+/// `dig.count` naming the thing and what `set` sets on it. Things come in
+/// the order they're first mentioned, starting from their defaults.
+pub fn parse_named<T, F>(data: &[u8], set: F) -> Result<Vec<(String, T)>, String>
+where
+    T: Default,
+    F: Fn(&mut T, &str, &str) -> Result<(), String>,
+{
+    let text = std::str::from_utf8(data).map_err(|e| e.to_string())?;
+    let mut named: Vec<(String, T)> = Vec::new();
+
+    for (line, entry) in parse(text) {
+        entry
+            .and_then(|(key, value)| {
+                let (name, property) = split_key(key)?;
+                let index = match named.iter().position(|(existing, _)| existing == name) {
+                    Some(index) => index,
+                    None => {
+                        named.push((name.into(), T::default()));
+                        named.len() - 1
+                    }
+                };
+                set(&mut named[index].1, property, value)
+            })
+            .map_err(|e| format!("line {}: {}", line, e))?;
+    }
+
+    Ok(named)
+}
+
+/// Splits keys like `dig.count` into the name and what's being set.
+fn split_key(key: &str) -> Result<(&str, &str), String> {
+    let mut parts = key.splitn(2, '.');
+    let name = parts.next().unwrap();
+    match parts.next() {
+        Some(property) => Ok((name, property)),
+        None => Err(format!("expected `name.property`, not {}", key)),
+    }
+}
+
+/// Where the game keeps its files, if the platform has a place for them.
+pub fn dir() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+    Some(base.join("sdl2-minesweeper"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(text: &str) -> Vec<(usize, &str, &str)> {
+        parse(text)
+            .into_iter()
+            .map(|(line, entry)| {
+                let (key, value) = entry.unwrap();
+                (line, key, value)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn keeps_colors() {
+        assert_eq!(entries("background = #abcdef"), [(1, "background", "#abcdef")]);
+        assert_eq!(
+            entries("colors = #ff6040 #ffd080"),
+            [(1, "colors", "#ff6040 #ffd080")],
+        );
+    }
+
+    #[test]
+    fn strips_comments() {
+        let text = "# a comment\n  # an indented one\nsize = 8 # pixels\nparticle = #ffd080 #\n";
+        assert_eq!(entries(text), [(3, "size", "8"), (4, "particle", "#ffd080")]);
+    }
+
+    #[test]
+    fn skips_blank_lines() {
+        assert_eq!(entries("\na = 1\n\n   \nb = 2\n"), [(2, "a", "1"), (5, "b", "2")]);
+    }
+
+    #[test]
+    fn rejects_lines_without_a_value() {
+        let parsed = parse("a = 1\noops\n");
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[1].0, 2);
+        assert!(parsed[1].1.is_err());
+    }
+
+    #[test]
+    fn groups_named_things() {
+        let set = |value: &mut Vec<String>, property: &str, v: &str| {
+            value.push(format!("{}={}", property, v));
+            Ok(())
+        };
+        let named = parse_named(b"b.x = 1\na.y = 2\nb.z = 3\n", set).unwrap();
+        assert_eq!(
+            named,
+            [
+                ("b".to_string(), vec!["x=1".to_string(), "z=3".to_string()]),
+                ("a".to_string(), vec!["y=2".to_string()]),
+            ],
+        );
+
+        let error = parse_named(b"a.x = 1\nnope = 2\n", set).unwrap_err();
+        assert!(error.starts_with("line 2:"), "{}", error);
+    }
+
+    #[test]
+    fn splits_keys() {
+        assert_eq!(split_key("dig.count"), Ok(("dig", "count")));
+        assert!(split_key("dig").is_err());
+    }
+}
@@ -0,0 +1,278 @@
+//! Particle effects, described in a `config` file so they can be tuned
+//! without touching the code. Each line sets one property of an effect,
+//! keyed by the effect's name and the property:
+//!
+//! ```text
+//! dig.count = 2 4
+//! dig.speed = 150 250
+//! dig.colors = #ffffff #a0a0a0
+//! ```
+//!
+//! Ranges are a minimum and a maximum, or a single number for both. Lists
+//! of colors, alphas and sizes are spread evenly over each particle's life.
+
+use crate::config;
+use crate::theme::{self, Color};
+use rand::Rng;
+
+/// Where the effects are defined.
+pub const EFFECTS: &str = "src/effects.txt";
+
+/// Where in the area they're emitted over particles start.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shape {
+    /// The center.
+    Point,
+    /// Anywhere in it.
+    Area,
+    /// Anywhere in the circle filling it.
+    Circle,
+    /// Along its top edge.
+    Top,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sprite {
+    Square,
+    /// A dot with soft edges.
+    Round,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Range {
+    pub min: f32,
+    pub max: f32,
+}
+
+impl Range {
+    fn new(value: f32) -> Self {
+        Self { min: value, max: value }
+    }
+
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> f32 {
+        if self.min < self.max {
+            rng.gen_range(self.min, self.max)
+        } else {
+            self.min
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Effect {
+    pub shape: Shape,
+    /// How many particles one emission makes.
+    pub count: Range,
+    /// In pixels per second, at a display scale of 1.
+    pub speed: Range,
+    /// Angles in radians, clockwise from straight up: the middle direction
+    /// particles head in, and how wide a spread around it they take.
+    pub direction: f32,
+    pub spread: f32,
+    /// Seconds each particle lives.
+    pub lifetime: Range,
+    pub colors: Vec<Color>,
+    /// Colors each particle picks one of, multiplied with `colors`. With
+    /// none, the particles only take `colors`.
+    pub palette: Vec<Color>,
+    pub alpha: Vec<f32>,
+    /// Width in pixels, at a display scale of 1.
+    pub size: Vec<f32>,
+    /// Height of each particle over its width.
+    pub aspect: f32,
+    /// Downwards acceleration, in pixels per second squared.
+    pub gravity: f32,
+    /// How much of their speed particles lose each second.
+    pub drag: f32,
+    /// Starting angle and angular velocity, in radians and radians per
+    /// second.
+    pub rotation: Range,
+    pub spin: Range,
+    pub sprite: Sprite,
+    /// Most particles of this effect alive at once.
+    pub limit: Option<usize>,
+}
+
+impl Default for Effect {
+    fn default() -> Self {
+        Self {
+            shape: Shape::Point,
+            count: Range::new(1.0),
+            speed: Range::new(0.0),
+            direction: 0.0,
+            spread: std::f32::consts::TAU,
+            lifetime: Range::new(1.0),
+            colors: vec![(0xff, 0xff, 0xff)],
+            palette: Vec::new(),
+            alpha: vec![1.0, 0.0],
+            size: vec![8.0],
+            aspect: 1.0,
+            gravity: 1000.0,
+            drag: 0.0,
+            rotation: Range::new(0.0),
+            spin: Range::new(0.0),
+            sprite: Sprite::Square,
+            limit: None,
+        }
+    }
+}
+
+impl Effect {
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "shape" => {
+                self.shape = match value {
+                    "point" => Shape::Point,
+                    "area" => Shape::Area,
+                    "circle" => Shape::Circle,
+                    "top" => Shape::Top,
+                    _ => return Err(format!("unknown shape {}", value)),
+                }
+            }
+            "sprite" => {
+                self.sprite = match value {
+                    "square" => Sprite::Square,
+                    "round" => Sprite::Round,
+                    _ => return Err(format!("unknown sprite {}", value)),
+                }
+            }
+            "count" => self.count = parse_range(value)?,
+            "speed" => self.speed = parse_range(value)?,
+            "direction" => self.direction = parse_number(value)?.to_radians(),
+            "spread" => self.spread = parse_number(value)?.to_radians(),
+            "lifetime" => self.lifetime = parse_range(value)?,
+            "colors" => self.colors = parse_list(value, theme::parse_color)?,
+            "palette" => self.palette = parse_list(value, theme::parse_color)?,
+            "alpha" => self.alpha = parse_list(value, parse_number)?,
+            "size" => self.size = parse_list(value, parse_number)?,
+            "aspect" => self.aspect = parse_number(value)?.max(0.0),
+            "gravity" => self.gravity = parse_number(value)?,
+            "drag" => self.drag = parse_number(value)?.max(0.0),
+            "rotation" => self.rotation = degrees(parse_range(value)?),
+            "spin" => self.spin = degrees(parse_range(value)?),
+            "limit" => {
+                self.limit = Some(value.parse().map_err(|_| format!("invalid limit {}", value))?);
+            }
+            _ => return Err(format!("unknown property {}", key)),
+        }
+
+        Ok(())
+    }
+
+    /// Color, alpha and size `t` of the way through a particle's life.
+    pub fn color_at(&self, t: f32) -> Color {
+        let (index, t) = between_stops(self.colors.len(), t);
+        let from = self.colors[index];
+        let to = self.colors[(index + 1).min(self.colors.len() - 1)];
+        let mix = |from: u8, to: u8| (from as f32 + (to as f32 - from as f32) * t) as u8;
+
+        (mix(from.0, to.0), mix(from.1, to.1), mix(from.2, to.2))
+    }
+
+    pub fn alpha_at(&self, t: f32) -> f32 {
+        gradient(&self.alpha, t).clamp(0.0, 1.0)
+    }
+
+    pub fn size_at(&self, t: f32) -> f32 {
+        gradient(&self.size, t).max(0.0)
+    }
+}
+
+/// Which of `count` evenly spaced stops `t` is after, and how far it is
+/// towards the next.
+fn between_stops(count: usize, t: f32) -> (usize, f32) {
+    if count < 2 {
+        return (0, 0.0);
+    }
+
+    let position = t.clamp(0.0, 1.0) * (count - 1) as f32;
+    let index = (position as usize).min(count - 2);
+    (index, position - index as f32)
+}
+
+/// Interpolates between evenly spaced `stops`.
+fn gradient(stops: &[f32], t: f32) -> f32 {
+    let (index, t) = between_stops(stops.len(), t);
+    let to = stops[(index + 1).min(stops.len() - 1)];
+    stops[index] + (to - stops[index]) * t
+}
+
+fn parse_number(value: &str) -> Result<f32, String> {
+    value
+        .parse::<f32>()
+        .ok()
+        .filter(|number| number.is_finite())
+        .ok_or_else(|| format!("invalid number {}", value))
+}
+
+fn parse_range(value: &str) -> Result<Range, String> {
+    let numbers = parse_list(value, parse_number)?;
+    match *numbers.as_slice() {
+        [value] => Ok(Range::new(value)),
+        [min, max] if min <= max => Ok(Range { min, max }),
+        _ => Err(format!("expected a number or a minimum and maximum, not {}", value)),
+    }
+}
+
+fn parse_list<T, F: Fn(&str) -> Result<T, String>>(value: &str, parse: F) -> Result<Vec<T>, String> {
+    let list = value.split_whitespace().map(parse).collect::<Result<Vec<T>, String>>()?;
+    if list.is_empty() {
+        return Err("expected at least one value".into());
+    }
+
+    Ok(list)
+}
+
+fn degrees(range: Range) -> Range {
+    Range {
+        min: range.min.to_radians(),
+        max: range.max.to_radians(),
+    }
+}
+
+/// Named effects, in the order they were first mentioned.
+#[derive(Debug, Clone, Default)]
+pub struct Effects {
+    effects: Vec<(String, Effect)>,
+}
+
+impl Effects {
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        Ok(Self {
+            effects: config::parse_named(data, Effect::set)?,
+        })
+    }
+
+    /// Where the effect is kept, for particles to refer back to it.
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.effects.iter().position(|(effect_name, _)| effect_name == name)
+    }
+
+    pub fn get(&self, index: usize) -> &Effect {
+        &self.effects[index].1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_embedded_effects() {
+        let effects = Effects::parse(include_bytes!("effects.txt")).unwrap();
+
+        for name in &["dig", "flag", "explosion", "smoke", "win"] {
+            assert!(effects.index_of(name).is_some(), "{} is missing", name);
+        }
+        let flag = effects.get(effects.index_of("flag").unwrap());
+        assert_eq!(flag.colors, [(0xff, 0x60, 0x40), (0xff, 0xd0, 0x80)]);
+        let win = effects.get(effects.index_of("win").unwrap());
+        assert_eq!(win.palette.len(), 5);
+    }
+
+    #[test]
+    fn reports_the_line_of_a_bad_value() {
+        let error = Effects::parse(b"dig.count = 2\ndig.speed = fast\n").unwrap_err();
+        assert!(error.starts_with("line 2:"), "{}", error);
+    }
+}
@@ -0,0 +1,72 @@
+//! Losing: the mine that was hit going off with a flash and a shake of the
+//! screen, then every other mine after it, rippling out across the board.
+
+use crate::cascade::Cascade;
+use rand::Rng;
+
+/// Delay before a mine goes off, per cell it is away from the first one,
+/// in seconds.
+const CHAIN_STEP: f32 = 0.08;
+/// Random extra delay, so mines at the same distance don't go off at once.
+const CHAIN_JITTER: f32 = 0.05;
+/// The chain is sped up to finish within this many seconds, however big
+/// the board.
+const MAX_CHAIN_TIME: f32 = 4.0;
+
+/// How much a shake of 1 moves the field, in pixels at a display scale
+/// of 1, and how much of that it loses each second.
+const MAX_SHAKE: f32 = 16.0;
+const SHAKE_DECAY: f32 = 1.5;
+/// How long a full flash takes to fade, in seconds.
+const FLASH_TIME: f32 = 0.25;
+
+#[derive(Debug, Default)]
+pub struct Explosions {
+    /// Mines still to go off.
+    chain: Cascade,
+    /// From 0 to 1; the field moves by its square, so small shakes stay
+    /// subtle.
+    shake: f32,
+    /// Direction the field is pushed this frame, from -1 to 1 on each axis.
+    shake_direction: (f32, f32),
+    flash: f32,
+}
+
+impl Explosions {
+    /// Shakes the screen and flashes, more for a bigger `strength` from 0
+    /// to 1.
+    pub fn explode(&mut self, strength: f32) {
+        self.shake = (self.shake + strength).min(1.0);
+        self.flash = self.flash.max(strength);
+    }
+
+    /// Sets off `mines` one after another, the nearest to `origin` first.
+    pub fn chain(&mut self, origin: (u16, u16), mines: Vec<(u16, u16)>) {
+        self.chain.start(origin, mines, CHAIN_STEP, CHAIN_JITTER, MAX_CHAIN_TIME);
+    }
+
+    /// Advances the shake and the chain, returning the mines that go off.
+    pub fn update(&mut self, delta: f32) -> Vec<(u16, u16)> {
+        self.shake = (self.shake - SHAKE_DECAY * delta).max(0.0);
+        self.flash = (self.flash - delta / FLASH_TIME).max(0.0);
+
+        let mut rng = rand::thread_rng();
+        self.shake_direction = (rng.gen_range(-1.0, 1.0), rng.gen_range(-1.0, 1.0));
+
+        self.chain.update(delta)
+    }
+
+    /// How far to move the field this frame, in drawable pixels.
+    pub fn shake_offset(&self, display_scale: f32) -> (i32, i32) {
+        let amount = self.shake * self.shake * MAX_SHAKE * display_scale;
+        (
+            (self.shake_direction.0 * amount).round() as i32,
+            (self.shake_direction.1 * amount).round() as i32,
+        )
+    }
+
+    /// How bright the flash is, from 0 to 1.
+    pub fn flash(&self) -> f32 {
+        self.flash
+    }
+}
@@ -1,26 +1,42 @@
+use crate::geometry::Geometry;
+
 #[derive(Debug, Clone)]
 pub struct Cell {
-    pub has_mine: bool,
+    pub mines: u8,
     pub revealed: bool,
-    pub flagged: bool,
-    pub neighboring_mines: u8,
+    pub flags: u8,
+    /// Sum of the mines in every neighbor.
+    pub neighboring_mines: u16,
 }
 
 impl Default for Cell {
     fn default() -> Self {
         Self {
-            has_mine: false,
+            mines: 0,
             revealed: false,
-            flagged: false,
+            flags: 0,
             neighboring_mines: 0,
         }
     }
 }
 
+impl Cell {
+    pub fn has_mine(&self) -> bool {
+        self.mines > 0
+    }
+
+    pub fn is_flagged(&self) -> bool {
+        self.flags > 0
+    }
+}
+
 #[derive(Debug)]
 pub enum RevealResult {
     Nothing,
-    Success(Vec<(u8, u8)>),
+    /// The cells uncovered, grouped by how many steps of the flood fill
+    /// they are from the click. A chord's cells start a step from the
+    /// chorded cell.
+    Success(Vec<Vec<(u16, u16)>>),
     Mine,
 }
 
@@ -34,39 +50,64 @@ pub enum ToggleFlagResult {
 #[derive(Debug)]
 pub struct Field {
     cells: Vec<Cell>,
-    width: u8,
-    height: u8,
+    width: u16,
+    height: u16,
+    geometry: Geometry,
 
-    mine_count: u16,
-    flagged_cells: u16,
+    mine_count: u32,
+    max_mines_per_cell: u8,
+    placed_flags: u32,
+    /// Cells without mines that are yet to be revealed.
+    hidden_safe_cells: u64,
 
     populated: bool,
 }
 
 impl Field {
-    pub fn new(width: u8, height: u8, mine_count: u16) -> Self {
+    /// With `max_mines_per_cell` above 1, cells can hold several mines and
+    /// take as many flags.
+    pub fn new(
+        width: u16,
+        height: u16,
+        mine_count: u32,
+        max_mines_per_cell: u8,
+        geometry: Geometry,
+    ) -> Self {
+        assert!(geometry.is_supported());
+        assert!(geometry.supports_size((width, height)));
+        assert!(max_mines_per_cell > 0);
+
         Self {
             cells: vec![Default::default(); width as usize * height as usize],
             width,
             height,
+            geometry,
 
             mine_count,
-            flagged_cells: 0,
+            max_mines_per_cell,
+            placed_flags: 0,
+            hidden_safe_cells: 0,
 
             populated: false,
         }
     }
 
-    pub fn new_populated(width: u8, height: u8, mine_count: u16) -> Self {
-        let mut field = Field::new(width, height, mine_count);
+    pub fn new_populated(
+        width: u16,
+        height: u16,
+        mine_count: u32,
+        max_mines_per_cell: u8,
+        geometry: Geometry,
+    ) -> Self {
+        let mut field = Field::new(width, height, mine_count, max_mines_per_cell, geometry);
         field.populate(None);
         field
     }
 
-    fn populate(&mut self, safe_cell: Option<(u8, u8)>) {
+    fn populate(&mut self, safe_cell: Option<(u16, u16)>) {
         let cell_count =
-            self.width as u16 * self.height as u16 - if safe_cell.is_some() { 1 } else { 0 };
-        assert!(self.mine_count <= cell_count);
+            self.width as u64 * self.height as u64 - if safe_cell.is_some() { 1 } else { 0 };
+        assert!(self.mine_count as u64 <= cell_count * self.max_mines_per_cell as u64);
 
         use rand::distributions::{Distribution, Uniform};
         let x_distr = Uniform::new(0, self.width);
@@ -83,120 +124,234 @@ impl Field {
                 }
             }
 
+            let max_mines_per_cell = self.max_mines_per_cell;
             let cell = self.get_cell_mut(x, y);
-            if cell.has_mine {
+            if cell.mines >= max_mines_per_cell {
                 continue;
             }
 
-            cell.has_mine = true;
-            for x in x.saturating_sub(1)..=(x + 1).min(self.width - 1) {
-                for y in y.saturating_sub(1)..=(y + 1).min(self.height - 1) {
-                    self.get_cell_mut(x, y).neighboring_mines += 1;
-                }
+            cell.mines += 1;
+            for (x, y) in self.neighbors(x, y) {
+                self.get_cell_mut(x, y).neighboring_mines += 1;
             }
             remaining -= 1;
         }
 
+        self.hidden_safe_cells = self.cells.iter().filter(|cell| !cell.has_mine()).count() as u64;
         self.populated = true;
     }
 
-    pub fn mine_count(&self) -> u16 {
+    pub fn mine_count(&self) -> u32 {
         self.mine_count
     }
 
-    pub fn flagged_cells(&self) -> u16 {
-        self.flagged_cells
+    /// Whether every cell without a mine has been revealed.
+    pub fn is_cleared(&self) -> bool {
+        self.populated && self.hidden_safe_cells == 0
+    }
+
+    /// How many cells without a mine are left to reveal, once the mines
+    /// have been placed.
+    pub fn hidden_safe_cells(&self) -> Option<u64> {
+        if self.populated {
+            Some(self.hidden_safe_cells)
+        } else {
+            None
+        }
+    }
+
+    pub fn max_mines_per_cell(&self) -> u8 {
+        self.max_mines_per_cell
     }
 
-    pub fn width(&self) -> u8 {
+    /// Total number of flags on the board, counting stacked ones.
+    pub fn placed_flags(&self) -> u32 {
+        self.placed_flags
+    }
+
+    pub fn width(&self) -> u16 {
         self.width
     }
 
-    pub fn height(&self) -> u8 {
+    pub fn height(&self) -> u16 {
         self.height
     }
 
-    pub fn size(&self) -> (u8, u8) {
+    pub fn size(&self) -> (u16, u16) {
         (self.width, self.height)
     }
 
-    pub fn get_cell(&self, x: u8, y: u8) -> &Cell {
+    pub fn geometry(&self) -> Geometry {
+        self.geometry
+    }
+
+    pub fn neighbors(&self, x: u16, y: u16) -> Vec<(u16, u16)> {
+        self.geometry.neighbors((x, y), self.size())
+    }
+
+    pub fn get_cell(&self, x: u16, y: u16) -> &Cell {
         &self.cells[self.cell_index(x, y)]
     }
 
-    fn get_cell_mut(&mut self, x: u8, y: u8) -> &mut Cell {
+    /// Positions of the cells that hold mines.
+    pub fn mines(&self) -> Vec<(u16, u16)> {
+        (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .filter(|&(x, y)| self.get_cell(x, y).has_mine())
+            .collect()
+    }
+
+    /// Shows the mine in a cell, without counting it as a reveal, for when
+    /// the game is over.
+    pub fn expose_mine(&mut self, x: u16, y: u16) {
+        let cell = self.get_cell_mut(x, y);
+        if cell.has_mine() {
+            cell.revealed = true;
+        }
+    }
+
+    /// Flags a mined cell with exactly as many flags as it has mines, for
+    /// showing the answers once the game is won.
+    pub fn flag_mine(&mut self, x: u16, y: u16) {
+        let cell = self.get_cell_mut(x, y);
+        if cell.revealed || !cell.has_mine() {
+            return;
+        }
+
+        let (old_flags, mines) = (cell.flags, cell.mines);
+        cell.flags = mines;
+        self.placed_flags = self.placed_flags + mines as u32 - old_flags as u32;
+    }
+
+    fn get_cell_mut(&mut self, x: u16, y: u16) -> &mut Cell {
         let index = self.cell_index(x, y);
         &mut self.cells[index]
     }
 
-    fn cell_index(&self, x: u8, y: u8) -> usize {
+    fn cell_index(&self, x: u16, y: u16) -> usize {
         assert!(x < self.width);
         assert!(y < self.height);
 
         x as usize + y as usize * self.width as usize
     }
 
-    fn reveal_recursive(&mut self, x: u8, y: u8, revealed: &mut Vec<(u8, u8)>) {
-        let cell = self.get_cell_mut(x, y);
-        if cell.revealed || cell.flagged {
-            return;
-        }
+    /// Reveals the area around an already revealed empty cell,
+    /// breadth-first, returning the cells a step at a time, starting with
+    /// this one.
+    fn flood_reveal(&mut self, x: u16, y: u16) -> Vec<Vec<(u16, u16)>> {
+        let mut steps = vec![vec![(x, y)]];
 
-        assert!(!cell.has_mine);
-        cell.revealed = true;
-        revealed.push((x, y));
+        loop {
+            let mut next = Vec::new();
+            for &(x, y) in steps.last().unwrap() {
+                if self.get_cell(x, y).neighboring_mines != 0 {
+                    continue;
+                }
+
+                for (x, y) in self.neighbors(x, y) {
+                    let cell = self.get_cell_mut(x, y);
+                    if cell.revealed || cell.is_flagged() {
+                        continue;
+                    }
 
-        if cell.neighboring_mines == 0 {
-            for x in x.saturating_sub(1)..=(x + 1).min(self.width - 1) {
-                for y in y.saturating_sub(1)..=(y + 1).min(self.height - 1) {
-                    self.reveal_recursive(x, y, revealed);
+                    assert!(!cell.has_mine());
+                    cell.revealed = true;
+                    self.hidden_safe_cells -= 1;
+                    next.push((x, y));
                 }
             }
+
+            if next.is_empty() {
+                return steps;
+            }
+            steps.push(next);
         }
     }
 
-    pub fn reveal(&mut self, x: u8, y: u8) -> RevealResult {
+    pub fn reveal(&mut self, x: u16, y: u16) -> RevealResult {
         if !self.populated {
             self.populate(Some((x, y)));
         }
 
         let cell = self.get_cell_mut(x, y);
-        if cell.revealed || cell.flagged {
+        if cell.revealed || cell.is_flagged() {
             return RevealResult::Nothing;
         }
 
         cell.revealed = true;
-        if cell.has_mine {
-            RevealResult::Mine
-        } else if cell.neighboring_mines == 0 {
-            let mut revealed = vec![(x, y)];
-                
-            for x in x.saturating_sub(1)..=(x + 1).min(self.width - 1) {
-                for y in y.saturating_sub(1)..=(y + 1).min(self.height - 1) {
-                    self.reveal_recursive(x, y, &mut revealed);
+        if cell.has_mine() {
+            return RevealResult::Mine;
+        }
+
+        let neighboring_mines = cell.neighboring_mines;
+        self.hidden_safe_cells -= 1;
+        if neighboring_mines == 0 {
+            RevealResult::Success(self.flood_reveal(x, y))
+        } else {
+            RevealResult::Success(vec![vec![(x, y)]])
+        }
+    }
+
+    /// Reveals every unflagged neighbor of a revealed number once as many
+    /// neighbors have been flagged as it shows.
+    pub fn chord(&mut self, x: u16, y: u16) -> RevealResult {
+        let cell = self.get_cell(x, y);
+        if !cell.revealed || cell.has_mine() {
+            return RevealResult::Nothing;
+        }
+
+        let neighbors = self.neighbors(x, y);
+        let flags: u16 = neighbors
+            .iter()
+            .map(|&(x, y)| self.get_cell(x, y).flags as u16)
+            .sum();
+        if flags != cell.neighboring_mines {
+            return RevealResult::Nothing;
+        }
+
+        // nothing at the chorded cell itself
+        let mut revealed = vec![Vec::new()];
+        let mut hit_mine = false;
+        for (x, y) in neighbors {
+            match self.reveal(x, y) {
+                RevealResult::Success(steps) => {
+                    for (step, cells) in steps.into_iter().enumerate() {
+                        if revealed.len() <= step + 1 {
+                            revealed.push(Vec::new());
+                        }
+                        revealed[step + 1].extend(cells);
+                    }
                 }
+                RevealResult::Mine => hit_mine = true,
+                RevealResult::Nothing => (),
             }
+        }
 
-            RevealResult::Success(revealed)
+        if hit_mine {
+            RevealResult::Mine
+        } else if revealed.len() == 1 {
+            RevealResult::Nothing
         } else {
-            RevealResult::Success(vec![(x, y)])
+            RevealResult::Success(revealed)
         }
     }
 
-    /// Only performs the toggle if the cell isn't
-    /// revealed. Returns true if so.
-    pub fn toggle_flag(&mut self, x: u8, y: u8) -> ToggleFlagResult {
+    /// Adds a flag to the cell, or clears them once it holds as many as a
+    /// cell can have mines. Only does anything if the cell isn't revealed.
+    pub fn toggle_flag(&mut self, x: u16, y: u16) -> ToggleFlagResult {
+        let max_mines_per_cell = self.max_mines_per_cell;
         let cell = self.get_cell_mut(x, y);
 
         if !cell.revealed {
-            cell.flagged ^= true;
-
-            if cell.flagged {
-                self.flagged_cells += 1;
+            if cell.flags < max_mines_per_cell {
+                cell.flags += 1;
+                self.placed_flags += 1;
 
                 ToggleFlagResult::Flagged
             } else {
-                self.flagged_cells -= 1;
+                let removed = cell.flags;
+                cell.flags = 0;
+                self.placed_flags -= removed as u32;
 
                 ToggleFlagResult::Unflagged
             }
@@ -205,3 +360,186 @@ impl Field {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A square field with mines exactly where given.
+    fn field(width: u16, height: u16, mines: &[(u16, u16)]) -> Field {
+        let mines: Vec<_> = mines.iter().map(|&pos| (pos, 1)).collect();
+        stacked_field(width, height, 1, &mines)
+    }
+
+    /// Likewise, with as many mines as given in each cell.
+    fn stacked_field(width: u16, height: u16, max_mines_per_cell: u8, mines: &[((u16, u16), u8)]) -> Field {
+        let mine_count = mines.iter().map(|&(_, count)| count as u32).sum();
+        let mut field = Field::new(width, height, mine_count, max_mines_per_cell, Geometry::default());
+        for &((x, y), count) in mines {
+            field.get_cell_mut(x, y).mines = count;
+            for (x, y) in field.neighbors(x, y) {
+                field.get_cell_mut(x, y).neighboring_mines += count as u16;
+            }
+        }
+        field.hidden_safe_cells = field.cells.iter().filter(|cell| !cell.has_mine()).count() as u64;
+        field.populated = true;
+        field
+    }
+
+    fn steps(result: RevealResult) -> Vec<Vec<(u16, u16)>> {
+        match result {
+            RevealResult::Success(steps) => steps,
+            other => panic!("expected cells, got {:?}", other),
+        }
+    }
+
+    fn revealed(result: RevealResult) -> Vec<(u16, u16)> {
+        steps(result).concat()
+    }
+
+    #[test]
+    fn floods_outward_from_an_empty_cell() {
+        let mut field = field(5, 5, &[(4, 4)]);
+        let steps = steps(field.reveal(0, 0));
+
+        assert_eq!(steps.iter().map(Vec::len).sum::<usize>(), 24);
+        assert_eq!(steps[0], vec![(0, 0)]);
+        for (step, cells) in steps.iter().enumerate() {
+            assert!(cells.iter().all(|&(x, y)| x.max(y) as usize == step), "{:?}", steps);
+        }
+        assert!(field.is_cleared());
+    }
+
+    #[test]
+    fn stops_flooding_at_numbers() {
+        let wall: Vec<_> = (0..5).map(|y| (2, y)).collect();
+        let mut field = field(5, 5, &wall);
+        let cells = revealed(field.reveal(0, 0));
+
+        assert_eq!(cells.len(), 10);
+        assert!(cells.iter().all(|&(x, _)| x < 2));
+        assert_eq!(field.hidden_safe_cells(), Some(10));
+        assert!(!field.is_cleared());
+    }
+
+    #[test]
+    fn floods_around_flags() {
+        let wall: Vec<_> = (0..5).map(|y| (2, y)).collect();
+        let mut field = field(5, 5, &wall);
+        field.toggle_flag(0, 4);
+        let cells = revealed(field.reveal(0, 0));
+
+        assert_eq!(cells.len(), 9);
+        assert!(!cells.contains(&(0, 4)));
+    }
+
+    #[test]
+    fn numbers_dont_flood() {
+        let mut field = field(5, 5, &[(4, 4)]);
+        assert_eq!(revealed(field.reveal(3, 3)), vec![(3, 3)]);
+        assert!(!field.is_cleared());
+    }
+
+    #[test]
+    fn isnt_cleared_before_the_mines_are_placed() {
+        let field = Field::new(3, 3, 0, 1, Geometry::default());
+        assert!(!field.is_cleared());
+    }
+
+    #[test]
+    fn sums_stacked_mines_into_numbers() {
+        let field = Field::new_populated(6, 6, 60, 3, Geometry::default());
+
+        assert_eq!(field.cells.iter().map(|cell| cell.mines as u32).sum::<u32>(), 60);
+        assert!(field.cells.iter().all(|cell| cell.mines <= 3));
+        for y in 0..6 {
+            for x in 0..6 {
+                let around: u16 = field
+                    .neighbors(x, y)
+                    .into_iter()
+                    .map(|(x, y)| field.get_cell(x, y).mines as u16)
+                    .sum();
+                assert_eq!(field.get_cell(x, y).neighboring_mines, around, "at {:?}", (x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn cycles_stacked_flags() {
+        let mut field = stacked_field(3, 3, 3, &[((0, 0), 2)]);
+
+        for flags in 1..=3 {
+            assert!(matches!(field.toggle_flag(1, 1), ToggleFlagResult::Flagged));
+            assert_eq!(field.get_cell(1, 1).flags, flags);
+            assert_eq!(field.placed_flags(), flags as u32);
+        }
+        field.toggle_flag(0, 0);
+        assert_eq!(field.placed_flags(), 4);
+
+        assert!(matches!(field.toggle_flag(1, 1), ToggleFlagResult::Unflagged));
+        assert_eq!(field.get_cell(1, 1).flags, 0);
+        assert_eq!(field.placed_flags(), 1);
+    }
+
+    #[test]
+    fn doesnt_flag_revealed_cells() {
+        let mut field = stacked_field(3, 3, 3, &[((0, 0), 2)]);
+        field.reveal(2, 2);
+        assert!(matches!(field.toggle_flag(2, 2), ToggleFlagResult::Nothing));
+        assert_eq!(field.placed_flags(), 0);
+    }
+
+    #[test]
+    fn chords_once_stacked_flags_match() {
+        let mut field = stacked_field(3, 3, 3, &[((0, 0), 2)]);
+        assert!(matches!(field.reveal(1, 1), RevealResult::Success(_)));
+        assert_eq!(field.get_cell(1, 1).neighboring_mines, 2);
+
+        field.toggle_flag(0, 0);
+        assert!(matches!(field.chord(1, 1), RevealResult::Nothing));
+
+        field.toggle_flag(0, 0);
+        let steps = steps(field.chord(1, 1));
+        assert!(steps[0].is_empty());
+        assert_eq!(steps.concat().len(), 7);
+        assert!(field.is_cleared());
+    }
+
+    #[test]
+    fn chords_into_stacked_mines_with_too_few_flags_elsewhere() {
+        let mut field = stacked_field(3, 3, 3, &[((0, 0), 2)]);
+        field.reveal(1, 1);
+        field.toggle_flag(2, 2);
+        field.toggle_flag(2, 2);
+
+        assert!(matches!(field.chord(1, 1), RevealResult::Mine));
+    }
+
+    #[test]
+    fn flags_mines_exactly() {
+        let mut field = stacked_field(3, 3, 3, &[((0, 0), 2), ((2, 2), 3)]);
+        for _ in 0..3 {
+            field.toggle_flag(0, 0);
+        }
+        field.toggle_flag(2, 2);
+        assert_eq!(field.placed_flags(), 4);
+
+        field.flag_mine(0, 0);
+        field.flag_mine(2, 2);
+        field.flag_mine(1, 1);
+        assert_eq!(field.get_cell(0, 0).flags, 2);
+        assert_eq!(field.get_cell(2, 2).flags, 3);
+        assert_eq!(field.get_cell(1, 1).flags, 0);
+        assert_eq!(field.placed_flags(), 5);
+    }
+
+
+    #[test]
+    fn chords_floods_a_step_further_out() {
+        let mut field = field(5, 1, &[(0, 0)]);
+        field.reveal(1, 0);
+        field.toggle_flag(0, 0);
+
+        assert_eq!(steps(field.chord(1, 0)), vec![vec![], vec![(2, 0)], vec![(3, 0)], vec![(4, 0)]]);
+    }
+}
@@ -0,0 +1,366 @@
+use crate::topology::Topology;
+
+/// The shape of the cells and how they are arranged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Grid {
+    /// Square cells, each touching eight others.
+    #[default]
+    Square,
+    /// Hexagonal cells in rows, odd rows shifted half a cell to the right.
+    /// Each cell touches six others.
+    Hex,
+}
+
+const SQUARE_OFFSETS: [(i32, i32); 8] = [
+    (-1, -1), (0, -1), (1, -1),
+    (-1, 0), (1, 0),
+    (-1, 1), (0, 1), (1, 1),
+];
+
+const HEX_EVEN_ROW_OFFSETS: [(i32, i32); 6] = [
+    (-1, -1), (0, -1),
+    (-1, 0), (1, 0),
+    (-1, 1), (0, 1),
+];
+
+const HEX_ODD_ROW_OFFSETS: [(i32, i32); 6] = [
+    (0, -1), (1, -1),
+    (-1, 0), (1, 0),
+    (0, 1), (1, 1),
+];
+
+impl Grid {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "square" => Some(Grid::Square),
+            "hex" => Some(Grid::Hex),
+            _ => None,
+        }
+    }
+
+    /// Offsets from a cell in the given row to each of its neighbors.
+    pub fn neighbor_offsets(self, y: i32) -> &'static [(i32, i32)] {
+        match self {
+            Grid::Square => &SQUARE_OFFSETS,
+            Grid::Hex if y.rem_euclid(2) == 0 => &HEX_EVEN_ROW_OFFSETS,
+            Grid::Hex => &HEX_ODD_ROW_OFFSETS,
+        }
+    }
+
+    /// Distance between the tops of two rows, in cell widths.
+    pub fn row_height(self) -> f32 {
+        match self {
+            Grid::Square => 1.0,
+            Grid::Hex => 3f32.sqrt() / 2.0,
+        }
+    }
+
+    /// Height of a cell, in cell widths. Hexagons have a point at the top
+    /// and bottom, which rows overlap.
+    pub fn cell_height(self) -> f32 {
+        match self {
+            Grid::Square => 1.0,
+            Grid::Hex => 2.0 / 3f32.sqrt(),
+        }
+    }
+
+    /// How far the given row is shifted to the right, in cells.
+    pub fn row_offset(self, y: i32) -> f32 {
+        match self {
+            Grid::Hex if y.rem_euclid(2) == 1 => 0.5,
+            _ => 0.0,
+        }
+    }
+}
+
+/// Largest radius accepted for `Neighborhood::Radius`, so that counts
+/// still fit in a `u8`.
+pub const MAX_RADIUS: u8 = 7;
+
+const CROSS_OFFSETS: [(i32, i32); 4] = [(0, -1), (-1, 0), (1, 0), (0, 1)];
+
+const KNIGHT_OFFSETS: [(i32, i32); 8] = [
+    (-1, -2), (1, -2),
+    (-2, -1), (2, -1),
+    (-2, 1), (2, 1),
+    (-1, 2), (1, 2),
+];
+
+/// Which cells a number counts mines in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Neighborhood {
+    /// The cells touching this one; the classic rule.
+    #[default]
+    Adjacent,
+    /// Only the four orthogonally adjacent cells. Square grids only.
+    Cross,
+    /// The cells a chess knight could jump to. Square grids only.
+    Knight,
+    /// Every cell within the given number of steps.
+    Radius(u8),
+}
+
+impl Neighborhood {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "adjacent" => Some(Neighborhood::Adjacent),
+            "cross" => Some(Neighborhood::Cross),
+            "knight" => Some(Neighborhood::Knight),
+            _ => {
+                let radius = name.strip_prefix("radius")?.parse().ok()?;
+                if (1..=MAX_RADIUS).contains(&radius) {
+                    Some(Neighborhood::Radius(radius))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+/// Distance between two cells of a hex grid, in steps.
+fn hex_distance(a: (i32, i32), b: (i32, i32)) -> i32 {
+    // convert the shifted rows to axial coordinates
+    let axial = |(x, y): (i32, i32)| (x - (y - y.rem_euclid(2)) / 2, y);
+    let (aq, ar) = axial(a);
+    let (bq, br) = axial(b);
+    let (dq, dr) = (aq - bq, ar - br);
+
+    (dq.abs() + dr.abs() + (dq + dr).abs()) / 2
+}
+
+/// Everything that decides which cells are adjacent to each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Geometry {
+    pub grid: Grid,
+    pub topology: Topology,
+    pub neighborhood: Neighborhood,
+}
+
+impl Geometry {
+    /// Checks that the neighborhood makes sense on the grid.
+    pub fn is_supported(&self) -> bool {
+        !(self.grid == Grid::Hex
+            && matches!(self.neighborhood, Neighborhood::Cross | Neighborhood::Knight))
+    }
+
+    /// Checks that a board of the given size can be laid out with this
+    /// geometry.
+    pub fn supports_size(&self, size: (u16, u16)) -> bool {
+        // the row shift has to line up across the wrapped edge
+        !(self.grid == Grid::Hex && self.topology.wraps_vertically() && size.1 % 2 == 1)
+    }
+
+    /// Returns the cells surrounding `pos`, excluding `pos` itself.
+    /// On small wrapped boards the same cell can be reached from several
+    /// directions; it is only listed once.
+    pub fn neighbors(&self, pos: (u16, u16), size: (u16, u16)) -> Vec<(u16, u16)> {
+        let offsets = self.neighbor_offsets(pos.1 as i32);
+
+        let mut neighbors = Vec::with_capacity(offsets.len());
+        for (dx, dy) in offsets {
+            if let Some(n) = self.topology.wrap((pos.0 as i32 + dx, pos.1 as i32 + dy), size) {
+                if n != pos && !neighbors.contains(&n) {
+                    neighbors.push(n);
+                }
+            }
+        }
+
+        neighbors
+    }
+
+    /// Offsets from a cell in the given row to each cell of its
+    /// neighborhood.
+    pub fn neighbor_offsets(&self, y: i32) -> Vec<(i32, i32)> {
+        match (self.neighborhood, self.grid) {
+            (Neighborhood::Adjacent, _) => self.grid.neighbor_offsets(y).to_vec(),
+            (Neighborhood::Cross, _) => CROSS_OFFSETS.to_vec(),
+            (Neighborhood::Knight, _) => KNIGHT_OFFSETS.to_vec(),
+
+            (Neighborhood::Radius(radius), Grid::Square) => {
+                let radius = radius as i32;
+                let mut offsets = Vec::new();
+                for dy in -radius..=radius {
+                    for dx in -radius..=radius {
+                        if (dx, dy) != (0, 0) {
+                            offsets.push((dx, dy));
+                        }
+                    }
+                }
+                offsets
+            }
+
+            (Neighborhood::Radius(radius), Grid::Hex) => {
+                let radius = radius as i32;
+                let mut offsets = Vec::new();
+                for dy in -radius..=radius {
+                    for dx in -radius - 1..=radius + 1 {
+                        let distance = hex_distance((0, y), (dx, y + dy));
+                        if distance > 0 && distance <= radius {
+                            offsets.push((dx, dy));
+                        }
+                    }
+                }
+                offsets
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn geometry(grid: Grid, topology: Topology, neighborhood: Neighborhood) -> Geometry {
+        Geometry {
+            grid,
+            topology,
+            neighborhood,
+        }
+    }
+
+    fn sorted(mut cells: Vec<(u16, u16)>) -> Vec<(u16, u16)> {
+        cells.sort_unstable();
+        cells
+    }
+
+    #[test]
+    fn counts_fewer_neighbors_on_flat_edges() {
+        let flat = Geometry::default();
+        assert_eq!(flat.neighbors((0, 0), (5, 5)).len(), 3);
+        assert_eq!(flat.neighbors((2, 0), (5, 5)).len(), 5);
+        assert_eq!(flat.neighbors((2, 2), (5, 5)).len(), 8);
+    }
+
+    #[test]
+    fn wraps_neighbors_around_a_torus() {
+        let torus = geometry(Grid::Square, Topology::Torus, Neighborhood::Adjacent);
+        let neighbors = sorted(torus.neighbors((0, 0), (5, 5)));
+        assert_eq!(
+            neighbors,
+            vec![(0, 1), (0, 4), (1, 0), (1, 1), (1, 4), (4, 0), (4, 1), (4, 4)]
+        );
+    }
+
+    #[test]
+    fn lists_neighbors_once_on_tiny_tori() {
+        let torus = geometry(Grid::Square, Topology::Torus, Neighborhood::Adjacent);
+
+        // every cell of a single column is reached up, down and diagonally
+        assert_eq!(sorted(torus.neighbors((0, 1), (1, 3))), vec![(0, 0), (0, 2)]);
+        assert_eq!(torus.neighbors((0, 0), (1, 1)), vec![]);
+        // on 2x2 the other three cells are reached several times each
+        assert_eq!(sorted(torus.neighbors((0, 0), (2, 2))), vec![(0, 1), (1, 0), (1, 1)]);
+    }
+
+    #[test]
+    fn shifts_the_neighbors_of_odd_hex_rows() {
+        let hex = geometry(Grid::Hex, Topology::Flat, Neighborhood::Adjacent);
+        assert_eq!(
+            sorted(hex.neighbors((2, 2), (5, 5))),
+            vec![(1, 1), (1, 2), (1, 3), (2, 1), (2, 3), (3, 2)]
+        );
+        assert_eq!(
+            sorted(hex.neighbors((2, 1), (5, 5))),
+            vec![(1, 1), (2, 0), (2, 2), (3, 0), (3, 1), (3, 2)]
+        );
+    }
+
+    #[test]
+    fn measures_hex_distances() {
+        assert_eq!(hex_distance((2, 2), (2, 2)), 0);
+        for &(x, y) in Grid::Hex.neighbor_offsets(2) {
+            assert_eq!(hex_distance((2, 2), (2 + x, 2 + y)), 1);
+        }
+        for &(x, y) in Grid::Hex.neighbor_offsets(1) {
+            assert_eq!(hex_distance((2, 1), (2 + x, 1 + y)), 1);
+        }
+        assert_eq!(hex_distance((0, 0), (3, 0)), 3);
+        assert_eq!(hex_distance((0, 0), (0, 2)), 2);
+        assert_eq!(hex_distance((0, 0), (1, 2)), 2);
+        assert_eq!(hex_distance((0, 0), (2, 2)), 3);
+        assert_eq!(hex_distance((3, 5), (0, 0)), hex_distance((0, 0), (3, 5)));
+    }
+
+    #[test]
+    fn needs_even_heights_for_hex_rows_to_wrap() {
+        let hex_torus = geometry(Grid::Hex, Topology::Torus, Neighborhood::Adjacent);
+        assert!(hex_torus.supports_size((5, 4)));
+        assert!(!hex_torus.supports_size((5, 5)));
+
+        let hex_cylinder = geometry(Grid::Hex, Topology::HorizontalCylinder, Neighborhood::Adjacent);
+        assert!(hex_cylinder.supports_size((5, 5)));
+        let square_torus = geometry(Grid::Square, Topology::Torus, Neighborhood::Adjacent);
+        assert!(square_torus.supports_size((5, 5)));
+    }
+
+    #[test]
+    fn wraps_hex_rows_in_step() {
+        let hex = geometry(Grid::Hex, Topology::Torus, Neighborhood::Adjacent);
+        // the last row is odd, so it's shifted towards the first
+        assert_eq!(
+            sorted(hex.neighbors((0, 3), (4, 4))),
+            vec![(0, 0), (0, 2), (1, 0), (1, 2), (1, 3), (3, 3)]
+        );
+    }
+
+
+    #[test]
+    fn lists_cross_and_knight_offsets() {
+        let cross = geometry(Grid::Square, Topology::Flat, Neighborhood::Cross);
+        assert_eq!(sorted(cross.neighbors((2, 2), (5, 5))), vec![(1, 2), (2, 1), (2, 3), (3, 2)]);
+
+        let knight = geometry(Grid::Square, Topology::Flat, Neighborhood::Knight);
+        assert_eq!(
+            sorted(knight.neighbors((2, 2), (5, 5))),
+            vec![(0, 1), (0, 3), (1, 0), (1, 4), (3, 0), (3, 4), (4, 1), (4, 3)]
+        );
+        assert_eq!(sorted(knight.neighbors((0, 0), (5, 5))), vec![(1, 2), (2, 1)]);
+    }
+
+    #[test]
+    fn fills_radius_neighborhoods() {
+        for radius in 1..=MAX_RADIUS {
+            let r = radius as usize;
+            let square = geometry(Grid::Square, Topology::Flat, Neighborhood::Radius(radius));
+            let offsets = square.neighbor_offsets(0);
+            assert_eq!(offsets.len(), (2 * r + 1) * (2 * r + 1) - 1);
+            assert!(offsets.iter().all(|&(x, y)| x.abs().max(y.abs()) <= radius as i32));
+
+            let hex = geometry(Grid::Hex, Topology::Flat, Neighborhood::Radius(radius));
+            for y in 0..2 {
+                let offsets = hex.neighbor_offsets(y);
+                assert_eq!(offsets.len(), 3 * r * (r + 1));
+                let distance = |&(dx, dy): &(i32, i32)| hex_distance((0, y), (dx, y + dy));
+                assert!(offsets.iter().map(distance).all(|d| (1..=radius as i32).contains(&d)));
+            }
+        }
+
+        // radius 1 is the same as the adjacent cells
+        let hex = geometry(Grid::Hex, Topology::Flat, Neighborhood::Radius(1));
+        for y in 0..2 {
+            let mut offsets = hex.neighbor_offsets(y);
+            let mut adjacent = Grid::Hex.neighbor_offsets(y).to_vec();
+            offsets.sort_unstable();
+            adjacent.sort_unstable();
+            assert_eq!(offsets, adjacent);
+        }
+    }
+
+    #[test]
+    fn parses_neighborhood_names() {
+        assert_eq!(Neighborhood::from_name("knight"), Some(Neighborhood::Knight));
+        assert_eq!(Neighborhood::from_name("radius3"), Some(Neighborhood::Radius(3)));
+        assert_eq!(Neighborhood::from_name("radius0"), None);
+        assert_eq!(Neighborhood::from_name("radius8"), None);
+        assert_eq!(Neighborhood::from_name("queen"), None);
+    }
+
+    #[test]
+    fn keeps_cross_and_knight_to_square_grids() {
+        assert!(!geometry(Grid::Hex, Topology::Flat, Neighborhood::Cross).is_supported());
+        assert!(!geometry(Grid::Hex, Topology::Flat, Neighborhood::Knight).is_supported());
+        assert!(geometry(Grid::Hex, Topology::Flat, Neighborhood::Radius(2)).is_supported());
+    }
+
+}
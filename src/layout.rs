@@ -1,77 +1,448 @@
+use crate::field::Field;
+use crate::geometry::Grid;
+use crate::textures::TILE_SIZE;
 use sdl2::rect::Rect;
+use std::ops::Range;
 
 const STATUS_BAR_HEIGHT: u32 = 32;
 
+/// How many cells of wrapped context are shown past each wrapping edge.
+const WRAP_CONTEXT: u16 = 1;
+
+/// Space kept around the field when it fits in the window, in pixels.
+const FIELD_MARGIN: u32 = 8;
+
+/// The camera won't zoom out past this cell size, in pixels, unless the
+/// whole board already fits at a larger one.
+const MIN_CELL_SIZE: f32 = 16.0;
+const MAX_CELL_SIZE: f32 = 256.0;
+
+/// Longest side of the minimap, in pixels.
+const MINIMAP_SIZE: u32 = 160;
+const MINIMAP_MARGIN: i32 = 8;
+
 #[derive(Debug)]
 pub struct FieldLayout {
-    field_size: (u8, u8),
+    field_size: (u16, u16),
+    grid: Grid,
+    context: (u16, u16),
+    viewport: Rect,
+    integer_scaling: bool,
+    /// Drawable pixels per window coordinate; the layout works in drawable
+    /// pixels so it stays sharp on high density displays.
+    display_scale: f32,
+
+    /// Cell size at which the camera is fully zoomed out.
+    base_cell_size: f32,
+    /// Multiplier over `base_cell_size`, at least 1.
+    zoom: f32,
+    /// The point at the center of the viewport, in cells and rows.
+    center: (f32, f32),
+
+    origin: (f32, f32),
+    cell_size: f32,
     field_rect: Rect,
-    cell_rects: Vec<Rect>,
     status_bar_rect: Rect,
 }
 
 impl FieldLayout {
-    pub fn new(window_size: (u32, u32), field_size: (u8, u8)) -> Self {
+    pub fn new(window_size: (u32, u32), field: &Field) -> Self {
         let mut layout = Self {
-            field_size,
+            field_size: field.size(),
+            grid: field.geometry().grid,
+            context: (0, 0),
+            viewport: Rect::new(0, 0, 1, 1),
+            integer_scaling: false,
+            display_scale: 1.0,
+
+            base_cell_size: 1.0,
+            zoom: 1.0,
+            center: (field.width() as f32 / 2.0, field.height() as f32 / 2.0),
+
+            origin: (0.0, 0.0),
+            cell_size: 1.0,
             field_rect: Rect::new(0, 0, 1, 1),
-            cell_rects: Vec::new(),
             status_bar_rect: Rect::new(0, 0, 1, 1),
         };
-        layout.recalculate(window_size, field_size);
+        layout.recalculate(window_size, field);
         layout
     }
 
-    pub fn recalculate(&mut self, window_size: (u32, u32), field_size: (u8, u8)) {
-        // NOTE: only does pillarboxing
+    pub fn recalculate(&mut self, window_size: (u32, u32), field: &Field) {
+        let field_size = field.size();
+        let topology = field.geometry().topology;
         self.field_size = field_size;
+        self.grid = field.geometry().grid;
+        self.context = (
+            if topology.wraps_horizontally() { WRAP_CONTEXT } else { 0 },
+            if topology.wraps_vertically() { WRAP_CONTEXT } else { 0 },
+        );
 
-        let field_display_height = window_size.1 - STATUS_BAR_HEIGHT;
-        let cell_size = field_display_height as f32 / field_size.1 as f32;
-        let field_display_width = cell_size * field_size.0 as f32;
+        // the window can be shorter than the status bar; rects are at least
+        // a pixel in size anyway
+        let status_bar_height = self.scaled(STATUS_BAR_HEIGHT as f32) as u32;
+        let display_height = window_size.1.saturating_sub(status_bar_height);
+        self.viewport = Rect::new(0, status_bar_height as i32, window_size.0, display_height);
+        self.status_bar_rect = Rect::new(0, 0, window_size.0, status_bar_height);
 
-        let field_display_left = (window_size.0 as f32 - field_display_width) / 2.0;
-        let field_display_top = STATUS_BAR_HEIGHT as f32;
-        
-        self.field_rect = Rect::new(
-            field_display_left as i32,
-            field_display_top as i32,
-            field_display_width as u32,
-            field_display_height,
+        // fit both axes, leaving the rest of the window as margins
+        let (content_width, content_height) = self.content_size();
+        let margin = self.scaled(FIELD_MARGIN as f32) as u32;
+        let available = (
+            self.viewport.width().saturating_sub(2 * margin),
+            self.viewport.height().saturating_sub(2 * margin),
         );
+        let fit_cell_size = (available.0 as f32 / content_width)
+            .min(available.1 as f32 / (content_height * self.grid.row_height()));
+        self.base_cell_size = fit_cell_size.max(self.scaled(MIN_CELL_SIZE));
 
-        let boundary_x = |x| {
-            (field_display_left + field_display_width*x as f32/field_size.0 as f32) as i32
-        };
-        let boundary_y = |y| {
-            (field_display_top + field_display_height as f32*y as f32/field_size.1 as f32) as i32
-        };
+        self.update_camera();
+    }
+
+    /// Width and height of the field and its context, in cells and rows.
+    fn content_size(&self) -> (f32, f32) {
+        // shifted rows stick out by half a cell
+        let row_shift = self.grid.row_offset(1);
+        (
+            self.field_size.0 as f32 + 2.0 * self.context.0 as f32 + row_shift,
+            self.field_size.1 as f32 + 2.0 * self.context.1 as f32 + self.overhang(),
+        )
+    }
+
+    /// How far past the next row's top each cell reaches, in rows.
+    fn overhang(&self) -> f32 {
+        self.grid.cell_height() / self.grid.row_height() - 1.0
+    }
+
+    /// Distance between the tops of two rows, in pixels.
+    fn row_height(&self) -> f32 {
+        self.cell_size * self.grid.row_height()
+    }
+
+    /// Clamps the camera and recomputes everything that depends on it.
+    fn update_camera(&mut self) {
+        let max_zoom = (self.scaled(MAX_CELL_SIZE) / self.base_cell_size).max(1.0);
+        self.zoom = self.zoom.max(1.0).min(max_zoom);
+        self.cell_size = self.base_cell_size * self.zoom;
+        if self.integer_scaling {
+            self.cell_size = snap_cell_size(self.cell_size);
+        }
 
-        self.cell_rects.clear();
-        for y in 0 .. field_size.1 {
-            for x in 0 .. field_size.0 {
-                self.cell_rects.push(Rect::new(
-                        boundary_x(x),
-                        boundary_y(y),
-                        (boundary_x(x+1) - boundary_x(x)) as u32,
-                        (boundary_y(y+1) - boundary_y(y)) as u32,
-                ));
+        // keep as much of the field in view as possible
+        let (content_width, content_height) = self.content_size();
+        let clamp_axis = |center: f32, context: u16, content: f32, view: u32, unit: f32| {
+            let start = -(context as f32);
+            let end = start + content;
+            let half_view = view as f32 / unit / 2.0;
+
+            if content <= 2.0 * half_view {
+                (start + end) / 2.0
+            } else {
+                center.max(start + half_view).min(end - half_view)
             }
+        };
+        self.center = (
+            clamp_axis(self.center.0, self.context.0, content_width, self.viewport.width(), self.cell_size),
+            clamp_axis(self.center.1, self.context.1, content_height, self.viewport.height(), self.row_height()),
+        );
+
+        let viewport_center = self.viewport.center();
+        self.origin = (
+            viewport_center.x() as f32 - self.center.0 * self.cell_size,
+            viewport_center.y() as f32 - self.center.1 * self.row_height(),
+        );
+        if self.integer_scaling {
+            self.origin = (self.origin.0.round(), self.origin.1.round());
         }
+
+        self.field_rect = Rect::new(
+            self.origin.0 as i32,
+            self.origin.1 as i32,
+            (self.cell_size * (self.field_size.0 as f32 + self.grid.row_offset(1))) as u32,
+            (self.row_height() * (self.field_size.1 as f32 + self.overhang())) as u32,
+        );
+    }
+
+    /// Takes effect on the next `recalculate`.
+    pub fn set_display_scale(&mut self, display_scale: f32) {
+        self.display_scale = display_scale;
+    }
+
+    /// Converts a length in window coordinates to drawable pixels.
+    fn scaled(&self, length: f32) -> f32 {
+        length * self.display_scale
+    }
+
+    pub fn integer_scaling(&self) -> bool {
+        self.integer_scaling
+    }
+
+    /// In integer scaling mode every cell is the same whole number of
+    /// pixels, and a whole multiple of the tile art's size once it is at
+    /// least that big, so the art stays crisp.
+    pub fn set_integer_scaling(&mut self, integer_scaling: bool) {
+        self.integer_scaling = integer_scaling;
+        self.update_camera();
+    }
+
+    /// Zooms by `factor`, keeping the point under the given window
+    /// coordinates in place.
+    pub fn zoom_at(&mut self, factor: f32, anchor: (i32, i32)) {
+        let before = self.to_cells(anchor);
+        self.zoom *= factor;
+        self.update_camera();
+        let after = self.to_cells(anchor);
+
+        self.center.0 += before.0 - after.0;
+        self.center.1 += before.1 - after.1;
+        self.update_camera();
+    }
+
+    /// Moves the camera so the field moves by the given amount of pixels.
+    pub fn pan(&mut self, dx: f32, dy: f32) {
+        self.center.0 -= dx / self.cell_size;
+        self.center.1 -= dy / self.row_height();
+        self.update_camera();
+    }
+
+    /// Centers the camera on a point of the field, in cells and rows.
+    pub fn look_at(&mut self, center: (f32, f32)) {
+        self.center = center;
+        self.update_camera();
+    }
+
+    /// Window coordinates of the top left corner of cell (0, 0), and the
+    /// size of a cell; together they map field space to the window.
+    pub fn transform(&self) -> ((f32, f32), f32) {
+        (self.origin, self.cell_size)
+    }
+
+    /// Converts window coordinates to cells and rows.
+    fn to_cells(&self, pos: (i32, i32)) -> (f32, f32) {
+        (
+            (pos.0 as f32 - self.origin.0) / self.cell_size,
+            (pos.1 as f32 - self.origin.1) / self.row_height(),
+        )
+    }
+
+    fn boundary_x(&self, x: i32, y: i32) -> i32 {
+        (self.origin.0 + self.cell_size*(x as f32 + self.grid.row_offset(y))) as i32
+    }
+
+    fn boundary_y(&self, y: i32) -> i32 {
+        (self.origin.1 + self.row_height()*y as f32) as i32
+    }
+
+    fn cell_bottom(&self, y: i32) -> i32 {
+        (self.origin.1 + self.row_height()*(y as f32 + self.overhang() + 1.0)) as i32
     }
 
     pub fn field_rect(&self) -> Rect {
         self.field_rect
     }
 
-    pub fn cell_rect(&self, cell_index: (u8, u8)) -> Rect {
+    /// The part of the window the field is drawn in.
+    pub fn viewport(&self) -> Rect {
+        self.viewport
+    }
+
+    /// Number of wrapped context cells shown on each side of the field.
+    pub fn context(&self) -> (u16, u16) {
+        self.context
+    }
+
+    pub fn cell_rect(&self, cell_index: (u16, u16)) -> Rect {
         assert!(cell_index.0 < self.field_size.0);
         assert!(cell_index.1 < self.field_size.1);
 
-        self.cell_rects[cell_index.0 as usize + self.field_size.0 as usize*cell_index.1 as usize]
+        self.unwrapped_cell_rect((cell_index.0 as i32, cell_index.1 as i32))
+    }
+
+    /// Like `cell_rect`, but also accepts positions in the wrapped context
+    /// around the field.
+    pub fn unwrapped_cell_rect(&self, pos: (i32, i32)) -> Rect {
+        Rect::new(
+            self.boundary_x(pos.0, pos.1),
+            self.boundary_y(pos.1),
+            (self.boundary_x(pos.0 + 1, pos.1) - self.boundary_x(pos.0, pos.1)) as u32,
+            (self.cell_bottom(pos.1) - self.boundary_y(pos.1)) as u32,
+        )
+    }
+
+    /// Maps window coordinates to a (possibly out of bounds) cell position,
+    /// or `None` if they lie outside both the field and its context, or
+    /// aren't on the field's part of the window.
+    pub fn cell_at(&self, x: i32, y: i32) -> Option<(i32, i32)> {
+        if !self.viewport.contains_point((x, y)) {
+            return None;
+        }
+        if let Some(minimap) = self.minimap_rect() {
+            if minimap.contains_point((x, y)) {
+                return None;
+            }
+        }
+
+        let (x, y) = match self.grid {
+            Grid::Square => {
+                let (x, y) = self.to_cells((x, y));
+                (x.floor() as i32, y.floor() as i32)
+            }
+            Grid::Hex => self.nearest_cell(self.to_cells((x, y))),
+        };
+
+        let (columns, rows) = self.cell_range();
+        if !columns.contains(&x) || !rows.contains(&y) {
+            return None;
+        }
+
+        Some((x, y))
+    }
+
+    /// The cell whose center is nearest to `pos`, in cells and rows. On a
+    /// hex grid, that's the hexagon `pos` is in.
+    fn nearest_cell(&self, pos: (f32, f32)) -> (i32, i32) {
+        let (row_height, cell_height) = (self.grid.row_height(), self.grid.cell_height());
+        let distance = |(x, y): (i32, i32)| {
+            let dx = x as f32 + self.grid.row_offset(y) + 0.5 - pos.0;
+            let dy = y as f32 * row_height + cell_height / 2.0 - pos.1 * row_height;
+            dx * dx + dy * dy
+        };
+
+        let row = pos.1.floor() as i32;
+        let mut candidates = Vec::with_capacity(9);
+        for y in row - 1..=row + 1 {
+            let column = (pos.0 - self.grid.row_offset(y)).floor() as i32;
+            candidates.extend((column - 1..=column + 1).map(|x| (x, y)));
+        }
+
+        candidates
+            .into_iter()
+            .min_by(|&a, &b| distance(a).partial_cmp(&distance(b)).unwrap())
+            .unwrap()
+    }
+
+    /// The columns and rows of the field and its context, in unwrapped
+    /// coordinates.
+    fn cell_range(&self) -> (Range<i32>, Range<i32>) {
+        let (cx, cy) = (self.context.0 as i32, self.context.1 as i32);
+        (
+            -cx..self.field_size.0 as i32 + cx,
+            -cy..self.field_size.1 as i32 + cy,
+        )
+    }
+
+    /// The part of the field in view, in cells and rows: left, top, width,
+    /// height.
+    pub fn view_in_cells(&self) -> (f32, f32, f32, f32) {
+        let (left, top) = self.to_cells((self.viewport.left(), self.viewport.top()));
+        let (right, bottom) = self.to_cells((self.viewport.right(), self.viewport.bottom()));
+
+        (left, top, right - left, bottom - top)
+    }
+
+    /// The columns and rows of the field and its context that are at least
+    /// partly in view, in unwrapped coordinates.
+    pub fn visible_cells(&self) -> (Range<i32>, Range<i32>) {
+        let (left, top, width, height) = self.view_in_cells();
+
+        // the shifted rows can poke one column further out either way
+        let columns = left.floor() as i32 - 1..(left + width).ceil() as i32 + 1;
+        let rows = (top - self.overhang()).floor() as i32..(top + height).ceil() as i32;
+
+        let (all_columns, all_rows) = self.cell_range();
+        (
+            columns.start.max(all_columns.start)..columns.end.min(all_columns.end),
+            rows.start.max(all_rows.start)..rows.end.min(all_rows.end),
+        )
+    }
+
+    /// Where the minimap goes, if the field doesn't fit in the viewport.
+    pub fn minimap_rect(&self) -> Option<Rect> {
+        let (_, _, width, height) = self.view_in_cells();
+        let (content_width, content_height) = self.content_size();
+        if content_width <= width && content_height <= height {
+            return None;
+        }
+
+        let (field_width, field_height) = (self.field_size.0 as u32, self.field_size.1 as u32);
+        let scale = self.scaled(MINIMAP_SIZE as f32) / field_width.max(field_height) as f32;
+        let size = (
+            ((field_width as f32 * scale) as u32).max(1),
+            ((field_height as f32 * scale) as u32).max(1),
+        );
+
+        let margin = self.scaled(MINIMAP_MARGIN as f32) as i32;
+        Some(Rect::new(
+            self.viewport.right() - margin - size.0 as i32,
+            self.viewport.bottom() - margin - size.1 as i32,
+            size.0,
+            size.1,
+        ))
     }
 
     pub fn status_bar_rect(&self) -> Rect {
         self.status_bar_rect
     }
 }
+
+/// Rounds a cell size down for integer scaling mode.
+fn snap_cell_size(cell_size: f32) -> f32 {
+    let tile_size = TILE_SIZE as f32;
+    if cell_size >= tile_size {
+        (cell_size / tile_size).floor() * tile_size
+    } else {
+        cell_size.floor().max(1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::Geometry;
+
+    fn layout(grid: Grid) -> FieldLayout {
+        let geometry = Geometry { grid, ..Default::default() };
+        FieldLayout::new((640, 480), &Field::new(10, 8, 10, 1, geometry))
+    }
+
+    #[test]
+    fn spaces_hex_rows_closer_than_their_height() {
+        let layout = layout(Grid::Hex);
+        let (first, second) = (layout.cell_rect((0, 0)), layout.cell_rect((0, 1)));
+
+        let width = first.width() as f32;
+        let spacing = (second.y() - first.y()) as f32;
+        assert!((spacing / width - 3f32.sqrt() / 2.0).abs() < 0.05, "{} {}", spacing, width);
+        assert!((first.height() as f32 / width - 2.0 / 3f32.sqrt()).abs() < 0.05);
+        assert_eq!(second.x() - first.x(), first.width() as i32 / 2);
+    }
+
+    #[test]
+    fn picks_the_hexagon_under_the_point() {
+        let layout = layout(Grid::Hex);
+        for (x, y) in [(0, 0), (4, 3), (9, 7), (3, 4)].iter().copied() {
+            let center = layout.cell_rect((x, y)).center();
+            assert_eq!(layout.cell_at(center.x(), center.y()), Some((x as i32, y as i32)));
+        }
+
+        // the corners of a cell's rect belong to the cells around it; odd
+        // rows are shifted to the right
+        let rect = layout.cell_rect((4, 3));
+        assert_eq!(layout.cell_at(rect.left() + 1, rect.top() + 1), Some((4, 2)));
+        assert_eq!(layout.cell_at(rect.right() - 2, rect.top() + 1), Some((5, 2)));
+        assert_eq!(layout.cell_at(rect.left() + 1, rect.bottom() - 2), Some((4, 4)));
+
+        // and above the first row there's nothing
+        let rect = layout.cell_rect((4, 0));
+        assert_eq!(layout.cell_at(rect.left() + 1, rect.top() + 1), None);
+    }
+
+    #[test]
+    fn picks_squares_by_their_rect() {
+        let layout = layout(Grid::Square);
+        let rect = layout.cell_rect((4, 3));
+        assert_eq!(layout.cell_at(rect.left(), rect.top()), Some((4, 3)));
+        assert_eq!(layout.cell_at(rect.right() - 1, rect.bottom() - 1), Some((4, 3)));
+        assert_eq!(rect.height(), rect.width());
+    }
+}
@@ -1,47 +1,199 @@
 pub mod field;
-use field::RevealResult;
+use field::{Cell, RevealResult, ToggleFlagResult};
 
 pub mod textures;
 use textures::Textures;
 
 pub mod audio;
-use audio::AudioCallback;
+use audio::{AudioCallback, Mixer};
 
 pub mod sfx;
-use sfx::SOUND_EFFECTS;
+use sfx::{SoundEffects, SoundEvent};
+
+pub mod music;
+use music::{Intensity, Music};
+
+pub mod sound;
+pub mod synth;
+pub mod ring;
 
 pub mod state;
 use state::State;
 
 pub mod particles;
-use particles::{Particle, ParticleManager};
+use particles::ParticleManager;
+
+pub mod effects;
+use effects::{Effects, EFFECTS};
+
+pub mod explosions;
+use explosions::Explosions;
+
+pub mod cascade;
+use cascade::Cascade;
+
+pub mod overlay;
+
+pub mod summary;
+use summary::Summary;
+
+pub mod reveal_wave;
+use reveal_wave::RevealWave;
 
 pub mod layout;
 use layout::FieldLayout;
 
-use std::time::Instant;
-use sdl2::{audio::AudioDevice, event::Event, render::WindowCanvas, Sdl};
+pub mod topology;
+
+pub mod geometry;
+
+pub mod font;
+
+pub mod options;
+use options::Options;
+
+pub mod minimap;
+use minimap::Minimap;
+
+pub mod tile_art;
+
+pub mod theme;
+use theme::{Colors, Theme};
+
+pub mod assets;
+use assets::Assets;
+
+pub mod config;
+
+pub mod settings;
+use settings::Settings;
+
+pub mod settings_screen;
+use settings_screen::SettingsScreen;
+
+pub mod records;
+use records::Records;
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use sdl2::{audio::AudioDevice, event::Event, keyboard::Keycode, mouse::MouseButton, rect::Rect, render::WindowCanvas, Sdl};
 
 const WINDOW_WIDTH: u32 = 640;
 const WINDOW_HEIGHT: u32 = 480;
 
+/// How much one notch of the mouse wheel zooms.
+const ZOOM_STEP: f32 = 1.25;
+/// Keyboard panning speed, in pixels per second.
+const KEY_PAN_SPEED: f32 = 600.0;
+/// The game advances in steps of this many seconds, however fast it draws.
+const TIMESTEP: f32 = 1.0 / 120.0;
+/// Longest time a frame catches up on, so a stall doesn't have it run
+/// many steps at once.
+const MAX_FRAME_TIME: f32 = 0.25;
+/// Shortest time between frames without vsync, and while the window isn't
+/// focused.
+const MIN_FRAME_TIME: Duration = Duration::from_micros(1_000_000 / 144);
+const UNFOCUSED_FRAME_TIME: Duration = Duration::from_micros(1_000_000 / 30);
+/// How long to wait for events while the window is hidden, in
+/// milliseconds, before updating anyway.
+const HIDDEN_WAIT: u32 = 100;
+
+/// How far to either side sounds from the edges of the view are panned.
+const PAN_WIDTH: f32 = 0.8;
+/// How hard the mine that was hit goes off, and each one in the chain
+/// after it, from 0 to 1.
+const FIRST_EXPLOSION: f32 = 1.0;
+const CHAINED_EXPLOSION: f32 = 0.35;
+/// Volume of each explosion in the chain, so a board full of them doesn't
+/// drown everything out.
+const CHAINED_EXPLOSION_GAIN: f32 = 0.5;
+const FLASH_COLOR: (u8, u8, u8) = (0xff, 0xf0, 0xc8);
+/// Alpha of the flash at its brightest.
+const FLASH_ALPHA: f32 = 160.0;
+/// Seconds between flags per cell of distance when the mines left are
+/// flagged after a win, the random extra delay for each, and the longest
+/// the whole cascade takes.
+const FLAG_CASCADE_STEP: f32 = 0.04;
+const FLAG_CASCADE_JITTER: f32 = 0.02;
+const MAX_FLAG_CASCADE_TIME: f32 = 2.0;
+/// Volume of each flag in the cascade.
+const CASCADE_FLAG_GAIN: f32 = 0.5;
+/// Volume of the ticks as a reveal wave spreads, and how much higher each
+/// step of it sounds, up to `MAX_WAVE_TICK_PITCH`.
+const WAVE_TICK_GAIN: f32 = 0.25;
+const WAVE_TICK_PITCH_STEP: f32 = 0.02;
+const MAX_WAVE_TICK_PITCH: f32 = 1.5;
+/// The music turns tense once no more than this fraction of the cells
+/// are left to reveal.
+const TENSE_FRACTION: f32 = 0.1;
+
+/// How far the mouse has to move, in pixels, before a middle click becomes
+/// a drag.
+const DRAG_THRESHOLD: i32 = 4;
+
+#[derive(Debug)]
+struct Drag {
+    button: MouseButton,
+    /// Dragging on the minimap moves the camera to the pointer instead.
+    on_minimap: bool,
+    distance: i32,
+}
+
 pub struct Game {
     sdl: Sdl,
     canvas: WindowCanvas,
     textures: Textures,
+    assets: Assets,
 
-    audio_device: AudioDevice<AudioCallback>,
+    /// `None` when playing without sound.
+    audio_device: Option<AudioDevice<AudioCallback>>,
+    mixer: Mixer,
+    sound_effects: SoundEffects,
+    music: Music,
 
     running: bool,
+    /// Whether the window can be seen and has the keyboard.
+    visible: bool,
+    focused: bool,
 
     layout: FieldLayout,
     state: State,
-    hovering: Option<(u8, u8)>,
+    /// Which kind of board this is, for its best time.
+    board: String,
+    records: Records,
+    hovering: Option<(u16, u16)>,
+    /// The last second of the timer that ticked.
+    ticked_second: u64,
     particle_manager: ParticleManager,
+    reveal_wave: RevealWave,
+    explosions: Explosions,
+    /// Mines being flagged after a win.
+    flag_cascade: Cascade,
+    /// Shown once the flag cascade is over.
+    summary: Option<Summary>,
+    minimap: Minimap,
+
+    settings: Settings,
+    settings_screen: SettingsScreen,
+
+    colors: Colors,
+    /// Themes `T` cycles through, after the built in one.
+    themes: Vec<PathBuf>,
+    /// `None` for the built in theme.
+    theme_index: Option<usize>,
+
+    /// Drawable pixels per window coordinate. Everything but SDL's own
+    /// events works in drawable pixels.
+    display_scale: f32,
+    /// In drawable pixels.
+    mouse_pos: (i32, i32),
+    drag: Option<Drag>,
+    held_keys: HashSet<Keycode>,
 }
 
 impl Game {
-    pub fn new() -> Self {
+    pub fn new(options: Options) -> Self {
         // so we can properly bind the textures
         sdl2::hint::set("SDL_RENDER_DRIVER", "opengl");
 
@@ -56,157 +208,617 @@ impl Game {
             .opengl()
             .hidden()
             .resizable()
+            .allow_highdpi()
             .build()
             .unwrap();
         let canvas = window.into_canvas().present_vsync().build().unwrap();
 
-        let textures = Textures::new(&canvas);
-
-        let audio = sdl.audio().unwrap();
-        let audio_device = AudioCallback::new_device(&audio);
-
-        let particle_manager = ParticleManager::new(&canvas);
-
-        let state = State::new();
-        let layout = FieldLayout::new((WINDOW_WIDTH, WINDOW_HEIGHT), state.field().size());
-
-        Game {
+        let assets = if options.dev_assets {
+            Assets::from_source_tree()
+        } else {
+            Assets::embedded()
+        };
+        let mut textures = Textures::new(&canvas, &assets, options.geometry.grid);
+
+        let settings = Settings::load();
+        let (audio_device, mixer) = if options.no_audio {
+            (None, Mixer::null())
+        } else {
+            AudioCallback::open(&sdl, settings.volume)
+        };
+        let sound_effects = SoundEffects::new(&assets, mixer.sample_rate());
+        let music = Music::discover(mixer.sample_rate());
+
+        let display_scale = display_scale(&canvas);
+
+        let mut particle_manager = ParticleManager::new(&canvas, assets.load(EFFECTS, Effects::parse));
+        particle_manager.set_scale(display_scale);
+
+        let state = State::new(options.create_field());
+        let board = options.board_name();
+        let mut layout = FieldLayout::new(canvas.window().drawable_size(), state.field());
+        layout.set_display_scale(display_scale);
+        layout.set_integer_scaling(options.integer_scaling);
+        layout.recalculate(canvas.window().drawable_size(), state.field());
+        let minimap = Minimap::new(&canvas, state.field());
+        textures.set_cell_size(layout.transform().1.ceil() as u32);
+
+        let mut themes = theme::discover();
+        let theme_index = options.theme.map(|path| {
+            themes.iter().position(|theme| *theme == path).unwrap_or_else(|| {
+                themes.push(path);
+                themes.len() - 1
+            })
+        });
+
+        let mut game = Game {
             sdl,
             canvas,
             textures,
+            assets,
 
             audio_device,
+            mixer,
+            sound_effects,
+            music,
 
             running: false,
+            visible: true,
+            focused: true,
 
             layout,
             state,
+            board,
+            records: Records::load(),
             hovering: None,
+            ticked_second: 0,
 
             particle_manager,
+            reveal_wave: RevealWave::default(),
+            explosions: Explosions::default(),
+            flag_cascade: Cascade::default(),
+            summary: None,
+            minimap,
+
+            settings,
+            settings_screen: SettingsScreen::default(),
+
+            colors: Colors::default(),
+            themes,
+            theme_index: None,
+
+            display_scale,
+            mouse_pos: (0, 0),
+            drag: None,
+            held_keys: HashSet::new(),
+        };
+
+        if theme_index.is_some() {
+            game.set_theme(theme_index);
         }
+
+        game
     }
 
     pub fn run(mut self) {
         self.canvas.window_mut().show();
-        self.audio_device.resume();
+        if let Some(audio_device) = &self.audio_device {
+            audio_device.resume();
+        }
         self.running = true;
 
-        let mut last_update = Instant::now();
+        // when presenting doesn't wait for the display, frames are paced
+        // here instead
+        let vsync_flag = sdl2::sys::SDL_RendererFlags::SDL_RENDERER_PRESENTVSYNC as u32;
+        let vsync = self.canvas.info().flags & vsync_flag != 0;
+
+        let mut last_frame = Instant::now();
+        let mut lag = 0.0;
         let mut event_pump = self.sdl.event_pump().unwrap();
         while self.running {
+            if !self.visible {
+                // nothing to draw, so sleep until something happens
+                if let Some(event) = event_pump.wait_event_timeout(HIDDEN_WAIT) {
+                    self.event_handler(event);
+                }
+            }
             for event in event_pump.poll_iter() {
                 self.event_handler(event);
             }
 
-            self.render();
             let now = Instant::now();
-            self.update((now - last_update).as_secs_f32());
-            last_update = now;
+            lag += (now - last_frame).as_secs_f32().min(MAX_FRAME_TIME);
+            last_frame = now;
+            while lag >= TIMESTEP {
+                self.update(TIMESTEP);
+                lag -= TIMESTEP;
+            }
+            self.update_frame();
+
+            if !self.visible {
+                continue;
+            }
+            self.render(lag / TIMESTEP);
+
+            let min_frame_time = if !self.focused {
+                Some(UNFOCUSED_FRAME_TIME)
+            } else if !vsync {
+                Some(MIN_FRAME_TIME)
+            } else {
+                None
+            };
+            if let Some(min_frame_time) = min_frame_time {
+                if let Some(rest) = min_frame_time.checked_sub(now.elapsed()) {
+                    std::thread::sleep(rest);
+                }
+            }
         }
     }
 
-    fn map_window_coords(&self, x: i32, y: i32) -> Option<(u8, u8)> {
-        let render_bounds = self.layout.field_rect();
+    /// Switches to the theme at `index` in `themes`, or the built in one.
+    /// A theme that fails to load leaves the current one on screen.
+    fn set_theme(&mut self, index: Option<usize>) {
+        self.theme_index = index;
+
+        let theme = match index {
+            Some(i) => {
+                let path = &self.themes[i];
+                match Theme::load(path) {
+                    Ok((theme, problems)) => {
+                        for problem in problems {
+                            eprintln!("theme {}: {}", path.display(), problem);
+                        }
+                        theme
+                    }
+                    Err(e) => {
+                        eprintln!("can't load theme {}: {}", path.display(), e);
+                        return;
+                    }
+                }
+            }
+            None => Theme::default(),
+        };
 
-        let x = x - render_bounds.x;
-        if x < 0 || x >= render_bounds.width() as i32 {
-            return None;
-        }
+        self.textures.set_theme(&theme);
+        self.particle_manager.set_color(theme.colors.particle);
+        self.colors = theme.colors;
+    }
 
-        let y = y - render_bounds.y;
-        if y < 0 || y >= render_bounds.height() as i32 {
-            return None;
-        }
+    fn map_window_coords(&self, x: i32, y: i32) -> Option<(u16, u16)> {
+        let field = self.state.field();
+        let pos = self.layout.cell_at(x, y)?;
 
-        Some((
-            (x as u32 * self.state.field().width() as u32 / render_bounds.width() as u32) as u8,
-            (y as u32 * self.state.field().height() as u32 / render_bounds.height() as u32) as u8,
-        ))
+        field.geometry().topology.wrap(pos, field.size())
+    }
+
+    /// Changes the camera through `f`, keeping everything that lives in
+    /// field space in place.
+    fn move_camera<F: FnOnce(&mut FieldLayout, &field::Field)>(&mut self, f: F) {
+        let (old_origin, old_cell_size) = self.layout.transform();
+        f(&mut self.layout, self.state.field());
+        let (origin, cell_size) = self.layout.transform();
+
+        let scale = cell_size / old_cell_size;
+        self.particle_manager.transform(
+            |(x, y)| {
+                (
+                    origin.0 + (x - old_origin.0) * scale,
+                    origin.1 + (y - old_origin.1) * scale,
+                )
+            },
+            scale,
+        );
+
+        self.textures.set_cell_size(cell_size.ceil() as u32);
+        self.hovering = self.map_window_coords(self.mouse_pos.0, self.mouse_pos.1);
     }
 
     fn event_handler(&mut self, event: Event) {
         use sdl2::event::WindowEvent;
-        use sdl2::mouse::MouseButton;
 
         match event {
             Event::Quit { .. } => {
+                // changes on the settings screen are saved as it closes,
+                // which quitting skips
+                if self.settings_screen.is_open() {
+                    self.settings.save();
+                }
                 self.running = false;
             }
 
             Event::Window { win_event, .. } => match win_event {
-                WindowEvent::Resized(w, h) => {
-                    self.layout.recalculate((w as u32, h as u32), self.state.field().size());
+                // moving to a display with a different density changes the
+                // drawable size without a resize
+                WindowEvent::Resized(..) | WindowEvent::SizeChanged(..) => {
+                    let display_scale = display_scale(&self.canvas);
+                    let drawable_size = self.canvas.window().drawable_size();
+
+                    self.display_scale = display_scale;
+                    self.particle_manager.set_scale(display_scale);
+                    self.move_camera(|layout, field| {
+                        layout.set_display_scale(display_scale);
+                        layout.recalculate(drawable_size, field);
+                    });
                 }
 
+                WindowEvent::Hidden | WindowEvent::Minimized => self.visible = false,
+                WindowEvent::Shown | WindowEvent::Restored | WindowEvent::Exposed => self.visible = true,
+                WindowEvent::FocusGained => self.focused = true,
+                WindowEvent::FocusLost => self.focused = false,
+
                 _ => (),
             }
 
-            Event::MouseMotion { x, y, .. } => {
-                self.hovering = self.map_window_coords(x, y);
+            Event::KeyDown { keycode: Some(keycode), .. } => {
+                self.held_keys.insert(keycode);
+
+                match keycode {
+                    Keycode::Escape if self.is_summary_shown() => {
+                        self.summary = None;
+                        return;
+                    }
+                    Keycode::Escape => {
+                        self.settings_screen.toggle();
+                        self.drag = None;
+                        if !self.settings_screen.is_open() {
+                            self.settings.save();
+                        }
+                        return;
+                    }
+                    Keycode::M => {
+                        self.settings.volume.muted = !self.settings.volume.muted;
+                        self.mixer.set_volume(self.settings.volume);
+                        self.settings.save();
+                        return;
+                    }
+                    _ => (),
+                }
+
+                if self.settings_screen.is_open() {
+                    if self.settings_screen.key_down(keycode, &mut self.settings) {
+                        self.mixer.set_volume(self.settings.volume);
+                    }
+                    return;
+                }
+
+                let center = self.layout.viewport().center();
+                match keycode {
+                    Keycode::Plus | Keycode::Equals | Keycode::KpPlus => {
+                        self.move_camera(|layout, _| layout.zoom_at(ZOOM_STEP, center.into()));
+                    }
+                    Keycode::Minus | Keycode::KpMinus => {
+                        self.move_camera(|layout, _| layout.zoom_at(1.0 / ZOOM_STEP, center.into()));
+                    }
+                    Keycode::I => {
+                        let integer_scaling = !self.layout.integer_scaling();
+                        self.move_camera(|layout, _| layout.set_integer_scaling(integer_scaling));
+                    }
+                    Keycode::T => {
+                        let next = match self.theme_index {
+                            None => 0,
+                            Some(i) => i + 1,
+                        };
+                        self.set_theme(Some(next).filter(|&i| i < self.themes.len()));
+                    }
+                    _ => (),
+                }
+            }
+
+            Event::KeyUp { keycode: Some(keycode), .. } => {
+                self.held_keys.remove(&keycode);
+            }
+
+            Event::MouseWheel { y, .. } if !self.settings_screen.is_open() => {
+                let anchor = self.mouse_pos;
+                self.move_camera(|layout, _| layout.zoom_at(ZOOM_STEP.powi(y), anchor));
+            }
+
+            Event::MouseMotion { x, y, xrel, yrel, .. } => {
+                let (x, y) = self.to_drawable(x, y);
+                self.mouse_pos = (x, y);
+
+                if let Some(drag) = &mut self.drag {
+                    drag.distance += xrel.abs() + yrel.abs();
+
+                    if drag.on_minimap {
+                        self.look_at_minimap(x, y);
+                    } else if drag.distance >= DRAG_THRESHOLD {
+                        let (dx, dy) = (xrel as f32 * self.display_scale, yrel as f32 * self.display_scale);
+                        self.move_camera(|layout, _| layout.pan(dx, dy));
+                    }
+                }
+
+                let hovering = self.map_window_coords(x, y);
+                if let Some(cell) = hovering {
+                    if hovering != self.hovering && self.settings.hover_ticks {
+                        self.play_at(SoundEvent::HoverTick, cell);
+                    }
+                }
+                self.hovering = hovering;
+            }
+
+            Event::MouseButtonDown { .. } if self.is_summary_shown() && !self.settings_screen.is_open() => {
+                self.summary = None;
             }
 
             Event::MouseButtonDown {
                 mouse_btn, x, y, ..
-            } => {
-                if let Some((x, y)) = self.map_window_coords(x, y) {
-                    if mouse_btn == MouseButton::Right {
-                        self.state.toggle_flag(x, y);
+            } if !self.settings_screen.is_open() => {
+                let (x, y) = self.to_drawable(x, y);
+                let on_minimap = self
+                    .layout
+                    .minimap_rect()
+                    .map(|rect| rect.contains_point((x, y)))
+                    .unwrap_or(false);
+                let panning = mouse_btn == MouseButton::Middle
+                    || (mouse_btn == MouseButton::Left && self.held_keys.contains(&Keycode::Space));
+
+                if mouse_btn == MouseButton::Left && on_minimap {
+                    self.drag = Some(Drag { button: mouse_btn, on_minimap, distance: 0 });
+                    self.look_at_minimap(x, y);
+                } else if panning {
+                    self.drag = Some(Drag { button: mouse_btn, on_minimap: false, distance: 0 });
+                } else if let Some((x, y)) = self.map_window_coords(x, y) {
+                    if mouse_btn == MouseButton::Right && !self.state.game_over() {
+                        let event = match self.state.toggle_flag(x, y) {
+                            ToggleFlagResult::Flagged => SoundEvent::Flag,
+                            ToggleFlagResult::Unflagged => SoundEvent::Unflag,
+                            ToggleFlagResult::Nothing => SoundEvent::WastedClick,
+                        };
+                        self.play_at(event, (x, y));
+                        if event == SoundEvent::Flag {
+                            self.particle_manager.emit("flag", self.layout.cell_rect((x, y)));
+                        }
+                        self.minimap.invalidate();
                     }
                 }
             }
 
             Event::MouseButtonUp {
                 mouse_btn, x, y, ..
-            } => {
-                if mouse_btn == MouseButton::Left {
-                    match self.map_window_coords(x, y) {
-                        None => (),
-                        Some((x, y)) => match self.state.reveal(x, y) {
-                            RevealResult::Success(revealed) => {
-                                let mut audio_callback = self.audio_device.lock();
-                                audio_callback.play_sound_effect(&SOUND_EFFECTS.dig);
-                                drop(audio_callback);
-
-                                let render_rect = self.layout.field_rect();
-
-                                use rand::Rng;
-                                let mut rng = rand::thread_rng();
-                                for (x, y) in revealed {
-                                    let px = render_rect.left() as f32 + (x as f32 + 0.5) / self.state.field().width() as f32 * render_rect.width() as f32;
-                                    let py = render_rect.top() as f32 + (y as f32 + 0.5) / self.state.field().height() as f32 * render_rect.height() as f32;
-
-                                    for _ in 0 .. rng.gen_range(2, 5) {
-                                        let pos = (px, py);
-
-                                        let direction = rng.gen_range(0.0, std::f32::consts::TAU);
-
-                                        let particle = Particle::new(pos, 0.75)
-                                            .with_direction(direction, 200.0);
-                                        self.particle_manager.spawn(particle);
-                                    }
-                                }
-                            }
-
-                            RevealResult::Mine => {
-                                let mut audio_callback = self.audio_device.lock();
-                                audio_callback.play_sound_effect(&SOUND_EFFECTS.boom);
-                                drop(audio_callback);
-                            }
-
-                            _ => (),
+            } if !self.settings_screen.is_open() => {
+                let (x, y) = self.to_drawable(x, y);
+                if let Some(drag) = &self.drag {
+                    if drag.button == mouse_btn {
+                        let clicked = !drag.on_minimap
+                            && drag.distance < DRAG_THRESHOLD
+                            && mouse_btn == MouseButton::Middle;
+                        self.drag = None;
+
+                        // a middle click that didn't drag still chords
+                        if !clicked {
+                            return;
                         }
                     }
                 }
+
+                let pos = match self.map_window_coords(x, y) {
+                    Some(pos) => pos,
+                    None => return,
+                };
+                if self.state.game_over() {
+                    return;
+                }
+
+                let chording = match mouse_btn {
+                    MouseButton::Left => self.state.field().get_cell(pos.0, pos.1).revealed,
+                    MouseButton::Middle => true,
+                    _ => return,
+                };
+                let result = if chording {
+                    self.state.chord(pos.0, pos.1)
+                } else {
+                    self.state.reveal(pos.0, pos.1)
+                };
+
+                if chording {
+                    if let RevealResult::Success(_) = result {
+                        self.play_at(SoundEvent::Chord, pos);
+                    }
+                }
+                self.handle_reveal(result, pos);
             }
 
             _ => (),
         }
     }
 
+    fn to_drawable(&self, x: i32, y: i32) -> (i32, i32) {
+        (
+            (x as f32 * self.display_scale) as i32,
+            (y as f32 * self.display_scale) as i32,
+        )
+    }
+
+    fn look_at_minimap(&mut self, x: i32, y: i32) {
+        if let Some(rect) = self.layout.minimap_rect() {
+            let center = Minimap::to_field(self.state.field(), rect, x, y);
+            self.move_camera(|layout, _| layout.look_at(center));
+        }
+    }
+
+    /// How a sound coming from `x`, in drawable pixels, should be panned.
+    fn pan_at(&self, x: f32) -> f32 {
+        let viewport = self.layout.viewport();
+        let offset = x - viewport.center().x() as f32;
+        (offset / (viewport.width() as f32 / 2.0)).clamp(-1.0, 1.0) * PAN_WIDTH
+    }
+
+    /// Plays the sound of something happening at a cell.
+    fn play_at(&self, event: SoundEvent, pos: (u16, u16)) {
+        let pan = self.pan_at(self.layout.cell_rect(pos).center().x() as f32);
+        self.mixer.play(self.sound_effects.voice(event).with_pan(pan));
+    }
+
+    /// Confetti, a fanfare, and flags on every mine, spreading out from
+    /// the cell at `pos` that won the game. The summary comes after.
+    fn celebrate(&mut self, pos: (u16, u16)) {
+        let time = self.state.timer();
+        let previous_best = self.records.best(&self.board);
+        let new_best = self.records.submit(&self.board, time);
+        let event = if new_best { SoundEvent::BestTime } else { SoundEvent::Win };
+        self.mixer.play(self.sound_effects.voice(event));
+
+        let (width, height) = self.canvas.window().drawable_size();
+        self.particle_manager.emit("win", Rect::new(0, 0, width, height));
+
+        let field = self.state.field();
+        let unflagged = field
+            .mines()
+            .into_iter()
+            .filter(|&(x, y)| field.get_cell(x, y).flags != field.get_cell(x, y).mines)
+            .collect();
+        self.flag_cascade
+            .start(pos, unflagged, FLAG_CASCADE_STEP, FLAG_CASCADE_JITTER, MAX_FLAG_CASCADE_TIME);
+
+        self.summary = Some(Summary {
+            time,
+            previous_best,
+            size: field.size(),
+            mines: field.mine_count(),
+            clicks: self.state.clicks(),
+        });
+    }
+
+    fn is_summary_shown(&self) -> bool {
+        self.summary.is_some() && self.flag_cascade.is_done()
+    }
+
+    /// Particles, a flash and a shake for the mine at `pos` going off.
+    fn blow_up(&mut self, pos: (u16, u16), strength: f32) {
+        let rect = self.layout.cell_rect(pos);
+        self.particle_manager.emit("explosion", rect);
+        self.particle_manager.emit("smoke", rect);
+        self.explosions.explode(strength);
+    }
+
+    /// Handles the result of clicking the cell at `pos`.
+    fn handle_reveal(&mut self, result: RevealResult, pos: (u16, u16)) {
+        match result {
+            RevealResult::Success(steps) => {
+                self.minimap.invalidate();
+
+                // big reveals sound from the middle of what they uncovered
+                let count = steps.iter().map(Vec::len).sum::<usize>();
+                let center_x = steps
+                    .iter()
+                    .flatten()
+                    .map(|&pos| self.layout.cell_rect(pos).center().x() as f32)
+                    .sum::<f32>()
+                    / count.max(1) as f32;
+                let pan = self.pan_at(center_x);
+                self.mixer.play(self.sound_effects.voice(SoundEvent::Dig).with_pan(pan));
+                if count > 1 {
+                    self.mixer.play(self.sound_effects.flood(count).with_pan(pan));
+                }
+
+                self.reveal_wave.start(self.state.field(), steps);
+
+                if self.state.is_won() {
+                    self.celebrate(pos);
+                }
+            }
+
+            RevealResult::Mine => {
+                self.minimap.invalidate();
+                self.play_at(SoundEvent::Boom, pos);
+
+                // a chord can set off several mines at once; the rest go
+                // off after them, except those that were found
+                let field = self.state.field();
+                let (hit, hidden): (Vec<_>, Vec<_>) = field
+                    .mines()
+                    .into_iter()
+                    .filter(|&(x, y)| field.get_cell(x, y).revealed || !field.get_cell(x, y).is_flagged())
+                    .partition(|&(x, y)| field.get_cell(x, y).revealed);
+                for cell in hit {
+                    self.blow_up(cell, FIRST_EXPLOSION);
+                }
+                self.explosions.chain(pos, hidden);
+            }
+
+            // a flagged or revealed cell, or a chord that had nothing to do
+            RevealResult::Nothing => self.play_at(SoundEvent::WastedClick, pos),
+        }
+    }
+
+    /// Advances the game by one step of `delta` seconds.
     fn update(&mut self, delta: f32) {
+        let pan_speed = KEY_PAN_SPEED * self.display_scale * delta;
+        let held = |keys: &[Keycode]| keys.iter().any(|key| self.held_keys.contains(key));
+        let mut pan = (0.0, 0.0);
+        if held(&[Keycode::Left, Keycode::A]) {
+            pan.0 += pan_speed;
+        }
+        if held(&[Keycode::Right, Keycode::D]) {
+            pan.0 -= pan_speed;
+        }
+        if held(&[Keycode::Up, Keycode::W]) {
+            pan.1 += pan_speed;
+        }
+        if held(&[Keycode::Down, Keycode::S]) {
+            pan.1 -= pan_speed;
+        }
+        if pan != (0.0, 0.0) && !self.settings_screen.is_open() {
+            self.move_camera(|layout, _| layout.pan(pan.0, pan.1));
+        }
+
+        for ((x, y), _) in self.reveal_wave.update(delta) {
+            // huge reveals would only spin here without spawning
+            if self.particle_manager.is_full() {
+                break;
+            }
+
+            // the cell's tile shattering, in the color of its number
+            let color = self.textures.debris_color(self.state.field().get_cell(x, y).neighboring_mines);
+            self.particle_manager.emit_colored("dig", self.layout.cell_rect((x, y)), color);
+        }
+        if let Some(step) = self.reveal_wave.tick() {
+            let pitch = (1.0 + step as f32 * WAVE_TICK_PITCH_STEP).min(MAX_WAVE_TICK_PITCH);
+            let voice = self.sound_effects.voice(SoundEvent::Dig);
+            let (gain, pitch) = (voice.gain() * WAVE_TICK_GAIN, voice.pitch() * pitch);
+            self.mixer.play(voice.with_gain(gain).with_pitch(pitch));
+        }
+
+        for (x, y) in self.explosions.update(delta) {
+            self.state.expose_mine(x, y);
+            self.minimap.invalidate();
+
+            self.blow_up((x, y), CHAINED_EXPLOSION);
+            let pan = self.pan_at(self.layout.cell_rect((x, y)).center().x() as f32);
+            let voice = self.sound_effects.voice(SoundEvent::Boom);
+            let gain = voice.gain() * CHAINED_EXPLOSION_GAIN;
+            self.mixer.play(voice.with_gain(gain).with_pan(pan));
+        }
+
+        for (x, y) in self.flag_cascade.update(delta) {
+            self.state.flag_mine(x, y);
+            self.minimap.invalidate();
+
+            self.particle_manager.emit("flag", self.layout.cell_rect((x, y)));
+            let pan = self.pan_at(self.layout.cell_rect((x, y)).center().x() as f32);
+            let voice = self.sound_effects.voice(SoundEvent::Flag);
+            let gain = voice.gain() * CASCADE_FLAG_GAIN;
+            self.mixer.play(voice.with_gain(gain).with_pan(pan));
+        }
+
+        self.particle_manager.update(delta);
+    }
+
+    /// What only needs doing once per frame, however many steps it took.
+    fn update_frame(&mut self) {
+        self.reload_changed_assets();
+        self.textures.update();
+        self.mixer.free_finished();
+
         let timer = self.state.timer().as_secs();
         let mines_remaining = self.state.mines_remaining();
+        if timer > self.ticked_second && !self.state.game_over() {
+            self.ticked_second = timer;
+            if self.settings.timer_ticks {
+                self.mixer.play(self.sound_effects.voice(SoundEvent::TimerTick));
+            }
+        }
 
         self.canvas
             .window_mut()
@@ -218,59 +830,163 @@ impl Game {
             ))
             .unwrap();
 
-        self.particle_manager.update(delta);
+        let field = self.state.field();
+        let tense = match field.hidden_safe_cells() {
+            Some(hidden) if !self.state.game_over() => {
+                let cells = field.width() as f32 * field.height() as f32;
+                hidden as f32 <= cells * TENSE_FRACTION
+            }
+            _ => false,
+        };
+        self.music.set_intensity(if tense { Intensity::Tense } else { Intensity::Calm });
+        self.music.update(&self.mixer);
+    }
+
+    fn reload_changed_assets(&mut self) {
+        let changed = self.assets.changed();
+        let is_sound = |name: &&str| name.starts_with("src/sfx/");
+        let is_effects = |name: &&str| *name == EFFECTS;
+        if changed.iter().any(|name| !is_sound(name) && !is_effects(name)) {
+            self.textures.reload(&self.assets);
+        }
+        if changed.iter().any(is_sound) {
+            self.sound_effects = SoundEffects::new(&self.assets, self.mixer.sample_rate());
+        }
+        if changed.iter().any(is_effects) {
+            // a mistake while tuning them shouldn't take the effects away
+            match Effects::parse(&self.assets.read(EFFECTS)) {
+                Ok(effects) => self.particle_manager.set_effects(effects),
+                Err(e) => eprintln!("can't load {}, keeping the old effects: {}", EFFECTS, e),
+            }
+        }
+    }
+
+    fn is_hovering(&self, x: u16, y: u16) -> bool {
+        self.hovering
+            .map(|(pressed_x, pressed_y)| x == pressed_x && y == pressed_y)
+            .unwrap_or(false)
     }
 
-    fn render(&mut self) {
-        self.canvas.set_draw_color((255, 0, 255));
+    /// Draws the game, `blend` of the way from the last step to the next.
+    fn render(&mut self, blend: f32) {
+        self.canvas.set_draw_color(self.colors.background);
         self.canvas.clear();
+        self.canvas.set_draw_color(self.colors.status_bar);
+        self.canvas.fill_rect(self.layout.status_bar_rect()).unwrap();
 
-        let field_width = self.state.field().width();
-        let field_height = self.state.field().height();
+        let field = self.state.field();
+        let (field_width, field_height) = field.size();
 
-        for x in 0..field_width {
-            for y in 0..field_height {
-                let cell = self.state.field().get_cell(x, y);
+        self.canvas.set_clip_rect(self.layout.viewport());
+        let (shake_x, shake_y) = self.explosions.shake_offset(self.display_scale);
 
-                let texture = if cell.revealed {
-                    if cell.has_mine {
-                        &self.textures.mine
-                    } else {
-                        &self.textures.numbers[cell.neighboring_mines as usize]
-                    }
-                } else if self
-                    .hovering
-                    .map(|(pressed_x, pressed_y)| x == pressed_x && y == pressed_y)
-                    .unwrap_or(false)
-                {
-                    if cell.flagged {
-                        &self.textures.hover_flag
-                    } else {
-                        &self.textures.hover
-                    }
-                } else if cell.flagged {
-                    &self.textures.flag
-                } else {
-                    &self.textures.unrevealed
+        // cells across wrapping edges are drawn too, but shaded
+        let mut shaded = Vec::new();
+
+        let (columns, rows) = self.layout.visible_cells();
+        for y in rows {
+            for x in columns.clone() {
+                let (wx, wy) = match field.geometry().topology.wrap((x, y), field.size()) {
+                    Some(pos) => pos,
+                    None => continue,
                 };
 
-                self.canvas
-                    .copy(
-                        texture,
-                        None,
-                        Some(self.layout.cell_rect((x, y))),
-                    )
-                    .unwrap();
+                let mut rect = self.layout.unwrapped_cell_rect((x, y));
+                rect.offset(shake_x, shake_y);
+                let hovering = self.is_hovering(wx, wy);
+                let cell = field.get_cell(wx, wy);
+                match self.reveal_wave.progress((wx, wy)) {
+                    Some(progress) => draw_flipping_cell(&mut self.canvas, &mut self.textures, cell, rect, progress),
+                    None => draw_cell(&mut self.canvas, &mut self.textures, cell, hovering, rect),
+                }
+
+                if x < 0 || x >= field_width as i32 || y < 0 || y >= field_height as i32 {
+                    shaded.push(rect);
+                }
+            }
+        }
+
+        for rect in shaded {
+            self.canvas.copy(self.textures.shade(), None, Some(rect)).unwrap();
+        }
+
+        self.particle_manager.render(&mut self.canvas, (shake_x, shake_y), blend);
+
+        if self.explosions.flash() > 0.0 {
+            let (r, g, b) = FLASH_COLOR;
+            self.canvas.set_blend_mode(sdl2::render::BlendMode::Blend);
+            self.canvas.set_draw_color((r, g, b, (self.explosions.flash() * FLASH_ALPHA) as u8));
+            self.canvas.fill_rect(self.layout.viewport()).unwrap();
+            self.canvas.set_blend_mode(sdl2::render::BlendMode::None);
+        }
+        self.canvas.set_clip_rect(None);
+
+        if let Some(rect) = self.layout.minimap_rect() {
+            self.canvas.set_blend_mode(sdl2::render::BlendMode::Blend);
+            self.minimap.render(
+                &mut self.canvas,
+                self.state.field(),
+                rect,
+                self.layout.view_in_cells(),
+            );
+            self.canvas.set_blend_mode(sdl2::render::BlendMode::None);
+        }
+
+        if self.is_summary_shown() && !self.settings_screen.is_open() {
+            let (width, height) = self.canvas.output_size().unwrap();
+            if let Some(summary) = &self.summary {
+                summary.render(&mut self.canvas, Rect::new(0, 0, width, height), self.display_scale);
             }
         }
 
-        self.particle_manager.render(&mut self.canvas);
+        if self.settings_screen.is_open() {
+            let (width, height) = self.canvas.output_size().unwrap();
+            self.settings_screen.render(
+                &mut self.canvas,
+                &self.settings,
+                Rect::new(0, 0, width, height),
+                self.display_scale,
+            );
+        }
 
         self.canvas.present();
     }
 }
 
+/// Drawable pixels per window coordinate, above 1 on high density displays.
+fn display_scale(canvas: &WindowCanvas) -> f32 {
+    let window_size = canvas.window().size();
+    let drawable_size = canvas.window().drawable_size();
+
+    drawable_size.0 as f32 / window_size.0.max(1) as f32
+}
+
+fn draw_cell(canvas: &mut WindowCanvas, textures: &mut Textures, cell: &Cell, hovering: bool, rect: Rect) {
+    canvas
+        .copy(textures.for_cell(cell, hovering), None, Some(rect))
+        .unwrap();
+
+    if let Some(overlay) = textures.overlay_for_cell(cell) {
+        canvas.copy(overlay, None, Some(rect)).unwrap();
+    }
+}
+
+/// Draws a cell partway through flipping open: its unrevealed side
+/// narrowing to nothing, then its revealed side widening.
+fn draw_flipping_cell(canvas: &mut WindowCanvas, textures: &mut Textures, cell: &Cell, rect: Rect, progress: f32) {
+    let (side, width) = if progress < 0.5 {
+        (Cell { revealed: false, ..cell.clone() }, 1.0 - progress * 2.0)
+    } else {
+        (cell.clone(), progress * 2.0 - 1.0)
+    };
+
+    let width = (rect.width() as f32 * width).round() as u32;
+    if width > 0 {
+        draw_cell(canvas, textures, &side, false, Rect::from_center(rect.center(), width, rect.height()));
+    }
+}
+
 fn main() {
-    let game = Game::new();
+    let game = Game::new(Options::from_args());
     game.run();
 }
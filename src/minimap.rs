@@ -0,0 +1,154 @@
+use crate::field::{Cell, Field};
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Texture, TextureCreator, WindowCanvas};
+use sdl2::video::WindowContext;
+
+/// Longest side of the minimap texture; bigger boards are downsampled.
+const MAX_RESOLUTION: u32 = 256;
+
+const UNREVEALED_COLOR: [u8; 3] = [0x44, 0x5e, 0x80];
+const REVEALED_COLOR: [u8; 3] = [0xb3, 0xc7, 0xb8];
+const FLAG_COLOR: [u8; 3] = [0xe6, 0x44, 0x3c];
+const MINE_COLOR: [u8; 3] = [0x16, 0x23, 0x3c];
+
+const BORDER_COLOR: (u8, u8, u8, u8) = (0x16, 0x23, 0x3c, 0xff);
+const VIEW_COLOR: (u8, u8, u8, u8) = (0xff, 0xff, 0xff, 0xff);
+const ALPHA: u8 = 0xc0;
+
+/// A downsampled overview of the whole board.
+pub struct Minimap {
+    _texture_creator: TextureCreator<WindowContext>,
+    texture: Texture,
+
+    /// How many cells a side each texel covers.
+    block_size: u16,
+    size: (u32, u32),
+    dirty: bool,
+}
+
+fn cell_color(cell: &Cell) -> [u8; 3] {
+    if cell.revealed {
+        if cell.has_mine() {
+            MINE_COLOR
+        } else {
+            REVEALED_COLOR
+        }
+    } else if cell.is_flagged() {
+        FLAG_COLOR
+    } else {
+        UNREVEALED_COLOR
+    }
+}
+
+impl Minimap {
+    pub fn new(canvas: &WindowCanvas, field: &Field) -> Self {
+        let (width, height) = field.size();
+        let longest = width.max(height) as u32;
+        let block_size = longest.div_ceil(MAX_RESOLUTION) as u16;
+        let size = (
+            (width as u32).div_ceil(block_size as u32),
+            (height as u32).div_ceil(block_size as u32),
+        );
+
+        let texture_creator = canvas.texture_creator();
+        let mut texture = texture_creator
+            .create_texture_streaming(PixelFormatEnum::RGB24, size.0, size.1)
+            .unwrap();
+        texture.set_blend_mode(BlendMode::Blend);
+        texture.set_alpha_mod(ALPHA);
+
+        Self {
+            _texture_creator: texture_creator,
+            texture,
+
+            block_size,
+            size,
+            dirty: true,
+        }
+    }
+
+    /// Marks the minimap for redrawing before the next time it is shown.
+    pub fn invalidate(&mut self) {
+        self.dirty = true;
+    }
+
+    fn update(&mut self, field: &Field) {
+        let block_size = self.block_size;
+        let texture_size = self.size;
+        let (width, height) = field.size();
+
+        self.texture
+            .with_lock(None, |pixels, pitch| {
+                for ty in 0..texture_size.1 as u16 {
+                    for tx in 0..texture_size.0 as u16 {
+                        // average the cells under this texel
+                        let mut sum = [0u32; 3];
+                        let mut count = 0;
+                        let ys = ty * block_size..(ty * block_size).saturating_add(block_size).min(height);
+                        for y in ys {
+                            let xs = tx * block_size..(tx * block_size).saturating_add(block_size).min(width);
+                            for x in xs {
+                                let color = cell_color(field.get_cell(x, y));
+                                for (s, c) in sum.iter_mut().zip(color.iter()) {
+                                    *s += *c as u32;
+                                }
+                                count += 1;
+                            }
+                        }
+
+                        let i = ty as usize * pitch + tx as usize * 3;
+                        for (p, s) in pixels[i..i + 3].iter_mut().zip(sum.iter()) {
+                            *p = (s / count.max(1)) as u8;
+                        }
+                    }
+                }
+            })
+            .unwrap();
+
+        self.dirty = false;
+    }
+
+    /// Draws the minimap into `rect`, outlining `view` (left, top, width,
+    /// height, in cells).
+    pub fn render(
+        &mut self,
+        canvas: &mut WindowCanvas,
+        field: &Field,
+        rect: Rect,
+        view: (f32, f32, f32, f32),
+    ) {
+        if self.dirty {
+            self.update(field);
+        }
+
+        canvas.copy(&self.texture, None, Some(rect)).unwrap();
+
+        canvas.set_draw_color(BORDER_COLOR);
+        canvas.draw_rect(rect).unwrap();
+
+        let scale = (
+            rect.width() as f32 / field.width() as f32,
+            rect.height() as f32 / field.height() as f32,
+        );
+        let view_rect = Rect::new(
+            rect.left() + (view.0 * scale.0) as i32,
+            rect.top() + (view.1 * scale.1) as i32,
+            ((view.2 * scale.0) as u32).max(1),
+            ((view.3 * scale.1) as u32).max(1),
+        );
+        if let Some(view_rect) = view_rect.intersection(rect) {
+            canvas.set_draw_color(VIEW_COLOR);
+            canvas.draw_rect(view_rect).unwrap();
+        }
+    }
+
+    /// Maps window coordinates inside `rect` to a point of the field, in
+    /// cells.
+    pub fn to_field(field: &Field, rect: Rect, x: i32, y: i32) -> (f32, f32) {
+        (
+            (x - rect.left()) as f32 / rect.width() as f32 * field.width() as f32,
+            (y - rect.top()) as f32 / rect.height() as f32 * field.height() as f32,
+        )
+    }
+}
@@ -0,0 +1,369 @@
+//! Background music, streamed from Vorbis files as it plays instead of
+//! being decoded up front like the sound effects.
+//!
+//! Tracks are `.ogg` files in the `music` directory, played in order and
+//! looped. Those in `music/tense` take over when the game gets close to
+//! the end. Switching tracks crossfades between them.
+
+use crate::audio::Mixer;
+use crate::ring::{self, Consumer, Producer};
+use lewton::inside_ogg::OggStreamReader;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+pub const MUSIC_DIR: &str = "music";
+/// Within `MUSIC_DIR`, the tracks for when the game is tense.
+const TENSE_DIR: &str = "tense";
+
+/// How long tracks take to fade into each other, in seconds.
+const CROSSFADE: f32 = 2.0;
+/// How many decoded chunks, each a Vorbis packet long, are kept ready
+/// ahead of playback.
+const CHUNKS_AHEAD: usize = 16;
+/// How many played chunks can wait to be refilled. The decoder only makes
+/// a new chunk when none are waiting, so there are never more than those
+/// ahead, the one being decoded, the one playing and the one just played.
+const CHUNKS_RECYCLED: usize = CHUNKS_AHEAD + 3;
+/// How long the decoder waits for room once it's `CHUNKS_AHEAD` ahead.
+const DECODER_WAIT: Duration = Duration::from_millis(5);
+/// How far from the end of a file its last page is looked for.
+const LAST_PAGE_SEARCH: u64 = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Intensity {
+    Calm,
+    Tense,
+}
+
+/// A track being decoded on its own thread, and played by the audio
+/// callback.
+#[derive(Debug)]
+pub struct Stream {
+    /// Stereo frames at the device's sample rate, interleaved. The decoder
+    /// waits for room rather than the audio callback ever waiting on it.
+    chunks: Consumer<Vec<f32>>,
+    /// Played chunks go back to the decoder to be refilled, so the audio
+    /// callback doesn't free them. This carries on after the decoder's
+    /// gone, and then the chunks are freed with the stream.
+    recycle: Producer<Vec<f32>>,
+    chunk: Vec<f32>,
+    position: usize,
+    /// The crossfade level, heading towards `target`.
+    fade: f32,
+    target: f32,
+    fade_step: f32,
+    finished: bool,
+}
+
+impl Stream {
+    /// Starts decoding `path` for a device playing at `sample_rate`. The
+    /// track fades in, and loops until it's faded out.
+    pub fn open(path: &Path, sample_rate: u32) -> Self {
+        let (sender, chunks) = ring::ring(CHUNKS_AHEAD);
+        let (recycle, recycled) = ring::ring(CHUNKS_RECYCLED);
+
+        let path = path.to_path_buf();
+        std::thread::spawn(move || {
+            if let Err(e) = decode(&path, sample_rate, sender, recycled) {
+                eprintln!("can't play {}: {}", path.display(), e);
+            }
+        });
+
+        Self {
+            chunks,
+            recycle,
+            chunk: Vec::new(),
+            position: 0,
+            fade: 0.0,
+            target: 1.0,
+            fade_step: 1.0 / (CROSSFADE * sample_rate as f32),
+            finished: false,
+        }
+    }
+
+    pub fn fade_out(&mut self) {
+        self.target = 0.0;
+    }
+
+    /// Whether the stream has nothing left to play, either because it's
+    /// faded out or because decoding stopped.
+    pub fn is_done(&self) -> bool {
+        self.finished || (self.target == 0.0 && self.fade == 0.0)
+    }
+
+    /// The next stereo frame. If the decoder has fallen behind, it's
+    /// silent until it catches up.
+    pub fn next_frame(&mut self) -> [f32; 2] {
+        if self.position >= self.chunk.len() {
+            // checked first, so that chunks pushed just before the decoder
+            // stopped still play
+            let stopped = self.chunks.is_abandoned();
+            match self.chunks.pop() {
+                Some(chunk) => {
+                    let played = std::mem::replace(&mut self.chunk, chunk);
+                    // there's always room, by the decoder only making as many
+                    // chunks as can be recycled
+                    let _ = self.recycle.push(played);
+                    self.position = 0;
+                }
+                None if stopped => {
+                    self.finished = true;
+                    return [0.0; 2];
+                }
+                None => return [0.0; 2],
+            }
+        }
+
+        if self.fade < self.target {
+            self.fade = (self.fade + self.fade_step).min(self.target);
+        } else if self.fade > self.target {
+            self.fade = (self.fade - self.fade_step).max(self.target);
+        }
+
+        let frame = [self.chunk[self.position], self.chunk[self.position + 1]];
+        self.position += 2;
+        [frame[0] * self.fade, frame[1] * self.fade]
+    }
+}
+
+/// Decodes the track over and over, until the stream is dropped.
+fn decode(
+    path: &Path,
+    sample_rate: u32,
+    chunks: Producer<Vec<f32>>,
+    recycled: Consumer<Vec<f32>>,
+) -> Result<(), String> {
+    // resampled by interpolating between the last frame and the next,
+    // carrying on across packets and loops
+    let mut previous = [0.0f32; 2];
+    let mut t = 0.0f64;
+
+    loop {
+        let file = File::open(path).map_err(|e| e.to_string())?;
+        let mut reader = OggStreamReader::new(BufReader::new(file)).map_err(|e| e.to_string())?;
+
+        let channels = reader.ident_hdr.audio_channels as usize;
+        let step = reader.ident_hdr.audio_sample_rate as f64 / sample_rate as f64;
+        if channels == 0 || step == 0.0 {
+            return Err("no channels or no sample rate".into());
+        }
+
+        let mut decoded = false;
+        while let Some(packet) = reader.read_dec_packet_itl().map_err(|e| e.to_string())? {
+            let mut chunk = recycled.pop().unwrap_or_default();
+            chunk.clear();
+            for frame in packet.chunks_exact(channels) {
+                let level = |channel: usize| frame[channel.min(channels - 1)] as f32 / i16::MAX as f32;
+                let current = [level(0), level(1)];
+
+                while t < 1.0 {
+                    let t32 = t as f32;
+                    chunk.push(previous[0] + (current[0] - previous[0]) * t32);
+                    chunk.push(previous[1] + (current[1] - previous[1]) * t32);
+                    t += step;
+                }
+                t -= 1.0;
+                previous = current;
+            }
+
+            if chunk.is_empty() {
+                continue;
+            }
+            decoded = true;
+            while let Err(unsent) = chunks.push(chunk) {
+                if chunks.is_abandoned() {
+                    // the stream is gone
+                    return Ok(());
+                }
+                chunk = unsent;
+                std::thread::sleep(DECODER_WAIT);
+            }
+        }
+
+        // an empty track would loop forever without playing anything
+        if !decoded {
+            return Ok(());
+        }
+    }
+}
+
+/// How long a track is, from the position of its last page and its sample
+/// rate.
+fn track_length(path: &Path) -> Option<Duration> {
+    let mut file = File::open(path).ok()?;
+
+    let mut header = [0; 64];
+    file.read_exact(&mut header).ok()?;
+    // the identification packet follows the first page's header
+    let packet = header.get(27 + header[26] as usize..)?;
+    if !packet.starts_with(b"\x01vorbis") {
+        return None;
+    }
+    let mut sample_rate = [0; 4];
+    sample_rate.copy_from_slice(packet.get(12..16)?);
+    let sample_rate = u32::from_le_bytes(sample_rate);
+
+    let size = file.seek(SeekFrom::End(0)).ok()?;
+    file.seek(SeekFrom::Start(size.saturating_sub(LAST_PAGE_SEARCH))).ok()?;
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail).ok()?;
+    let last_page = tail.windows(4).rposition(|window| window == b"OggS")?;
+    let mut granule = [0; 8];
+    granule.copy_from_slice(tail.get(last_page + 6..last_page + 14)?);
+
+    if sample_rate == 0 {
+        return None;
+    }
+    Some(Duration::from_secs_f64(u64::from_le_bytes(granule) as f64 / sample_rate as f64))
+}
+
+/// The track playing.
+struct Playing {
+    intensity: Intensity,
+    index: usize,
+    started: Instant,
+    length: Option<Duration>,
+}
+
+/// Picks what plays, and when to move on to the next track.
+pub struct Music {
+    calm: Vec<PathBuf>,
+    tense: Vec<PathBuf>,
+    intensity: Intensity,
+    playing: Option<Playing>,
+    sample_rate: u32,
+}
+
+impl Music {
+    /// Finds the tracks in `MUSIC_DIR`, to be played at `sample_rate`.
+    pub fn discover(sample_rate: u32) -> Self {
+        let music_dir = Path::new(MUSIC_DIR);
+
+        Self {
+            calm: playlist(music_dir),
+            tense: playlist(&music_dir.join(TENSE_DIR)),
+            intensity: Intensity::Calm,
+            playing: None,
+            sample_rate,
+        }
+    }
+
+    /// Switches to the tracks for `intensity`, if there are any, from the
+    /// next `update`.
+    pub fn set_intensity(&mut self, intensity: Intensity) {
+        self.intensity = intensity;
+    }
+
+    /// Starts the next track when the one playing is about to end or the
+    /// intensity changed. Without audio, no tracks are decoded at all.
+    pub fn update(&mut self, mixer: &Mixer) {
+        if mixer.is_null() {
+            return;
+        }
+
+        let intensity = if self.intensity == Intensity::Tense && !self.tense.is_empty() {
+            Intensity::Tense
+        } else {
+            Intensity::Calm
+        };
+        let tracks = match intensity {
+            Intensity::Calm => &self.calm,
+            Intensity::Tense => &self.tense,
+        };
+        if tracks.is_empty() {
+            return;
+        }
+
+        let index = match &self.playing {
+            None => 0,
+            Some(playing) if playing.intensity != intensity => 0,
+            // a single track just loops
+            Some(playing) if tracks.len() > 1 => {
+                let crossfade = Duration::from_secs_f32(CROSSFADE);
+                match playing.length {
+                    Some(length) if playing.started.elapsed() + crossfade >= length => {
+                        (playing.index + 1) % tracks.len()
+                    }
+                    _ => return,
+                }
+            }
+            Some(_) => return,
+        };
+
+        let path = &tracks[index];
+        mixer.play_music(Stream::open(path, self.sample_rate));
+        self.playing = Some(Playing {
+            intensity,
+            index,
+            started: Instant::now(),
+            length: track_length(path),
+        });
+    }
+}
+
+/// The `.ogg` files in `dir`, sorted.
+fn playlist(dir: &Path) -> Vec<PathBuf> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut tracks: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().map(|ext| ext == "ogg").unwrap_or(false))
+        .collect();
+    tracks.sort();
+
+    tracks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plays_nothing_without_audio() {
+        let mut music = Music {
+            calm: vec![PathBuf::from("calm.ogg")],
+            tense: Vec::new(),
+            intensity: Intensity::Calm,
+            playing: None,
+            sample_rate: 44100,
+        };
+
+        music.update(&Mixer::null());
+        assert!(music.playing.is_none());
+    }
+
+    #[test]
+    fn stops_once_the_decoder_has_nothing_left() {
+        let (chunks, received) = ring::ring(CHUNKS_AHEAD);
+        let (recycle, recycled) = ring::ring(CHUNKS_RECYCLED);
+        let mut stream = Stream {
+            chunks: received,
+            recycle,
+            chunk: Vec::new(),
+            position: 0,
+            fade: 1.0,
+            target: 1.0,
+            fade_step: 0.1,
+            finished: false,
+        };
+
+        assert_eq!(stream.next_frame(), [0.0; 2]);
+        assert!(!stream.is_done());
+
+        chunks.push(vec![0.5, -0.5]).unwrap();
+        drop(chunks);
+        assert_eq!(stream.next_frame(), [0.5, -0.5]);
+        assert!(!stream.is_done());
+        assert_eq!(stream.next_frame(), [0.0; 2]);
+        assert!(stream.is_done());
+
+        // the first chunk went back to be refilled
+        assert_eq!(recycled.pop(), Some(Vec::new()));
+    }
+}
@@ -0,0 +1,249 @@
+use crate::field::Field;
+use crate::geometry::{Geometry, Grid, Neighborhood};
+use crate::topology::Topology;
+use std::convert::TryFrom;
+use std::path::PathBuf;
+
+const DEFAULT_SIZE: (u16, u16) = (8, 8);
+const DEFAULT_MINE_COUNT: u32 = 10;
+/// Most cells a board may have, so a typo in `--size` can't ask for
+/// gigabytes of them.
+const MAX_CELLS: u64 = 1 << 24;
+
+const USAGE: &str = "usage: sdl2-minesweeper [--grid square|hex] \
+[--topology flat|torus|hcylinder|vcylinder] \
+[--neighborhood adjacent|cross|knight|radius<N>] [--mines-per-cell <N>] \
+[--size <W>x<H>] [--mines <N>] [--integer-scaling] [--theme <path>] [--dev-assets] [--no-audio]";
+
+/// Settings picked on the command line.
+#[derive(Debug)]
+pub struct Options {
+    pub geometry: Geometry,
+    pub mines_per_cell: u8,
+    pub size: (u16, u16),
+    /// Defaults to 10 per 64 cells, times `mines_per_cell` so that stacked
+    /// mines don't make the board emptier.
+    pub mine_count: Option<u32>,
+    pub integer_scaling: bool,
+    /// A theme directory or zip archive.
+    pub theme: Option<PathBuf>,
+    /// Load the art and sounds from the source tree, reloading them when
+    /// they change.
+    pub dev_assets: bool,
+    pub no_audio: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            geometry: Default::default(),
+            mines_per_cell: 1,
+            size: DEFAULT_SIZE,
+            mine_count: None,
+            integer_scaling: false,
+            theme: None,
+            dev_assets: false,
+            no_audio: false,
+        }
+    }
+}
+
+impl Options {
+    /// Parses the process arguments, exiting with a usage message if they
+    /// are invalid.
+    pub fn from_args() -> Self {
+        match Options::parse(std::env::args().skip(1)) {
+            Ok(options) => options,
+            Err(message) => {
+                eprintln!("{}\n{}", message, USAGE);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Self, String> {
+        let mut options = Options::default();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--grid" => {
+                    let value = args.next().ok_or("--grid needs a value")?;
+                    options.geometry.grid = Grid::from_name(&value)
+                        .ok_or_else(|| format!("unknown grid: {}", value))?;
+                }
+
+                "--topology" => {
+                    let value = args.next().ok_or("--topology needs a value")?;
+                    options.geometry.topology = Topology::from_name(&value)
+                        .ok_or_else(|| format!("unknown topology: {}", value))?;
+                }
+
+                "--neighborhood" => {
+                    let value = args.next().ok_or("--neighborhood needs a value")?;
+                    options.geometry.neighborhood = Neighborhood::from_name(&value)
+                        .ok_or_else(|| format!("unknown neighborhood: {}", value))?;
+                }
+
+                "--mines-per-cell" => {
+                    let value = args.next().ok_or("--mines-per-cell needs a value")?;
+                    options.mines_per_cell = value
+                        .parse()
+                        .ok()
+                        .filter(|&n| n > 0)
+                        .ok_or_else(|| format!("invalid mines per cell: {}", value))?;
+                }
+
+                "--size" => {
+                    let value = args.next().ok_or("--size needs a value")?;
+                    options.size = parse_size(&value)
+                        .ok_or_else(|| format!("invalid size: {}", value))?;
+                }
+
+                "--mines" => {
+                    let value = args.next().ok_or("--mines needs a value")?;
+                    options.mine_count = Some(
+                        value
+                            .parse()
+                            .map_err(|_| format!("invalid mine count: {}", value))?,
+                    );
+                }
+
+                "--integer-scaling" => options.integer_scaling = true,
+
+                "--theme" => {
+                    let value = args.next().ok_or("--theme needs a value")?;
+                    options.theme = Some(value.into());
+                }
+
+                "--dev-assets" => options.dev_assets = true,
+
+                "--no-audio" => options.no_audio = true,
+
+                "-h" | "--help" => {
+                    println!("{}", USAGE);
+                    std::process::exit(0);
+                }
+
+                _ => return Err(format!("unknown argument: {}", arg)),
+            }
+        }
+
+        if !options.geometry.is_supported() {
+            return Err("that neighborhood isn't available on hex grids".into());
+        }
+        if !options.geometry.supports_size(options.size) {
+            return Err("wrapped hex grids need an even height".into());
+        }
+
+        let cells = options.size.0 as u64 * options.size.1 as u64;
+        if cells > MAX_CELLS {
+            return Err(format!("boards can have at most {} cells", MAX_CELLS));
+        }
+
+        // one cell always stays clear for the first click
+        let mine_count = options.mine_count()?;
+        if mine_count as u64 > (cells - 1) * options.mines_per_cell as u64 {
+            return Err("too many mines for the board".into());
+        }
+        options.mine_count = Some(mine_count);
+
+        Ok(options)
+    }
+
+    /// The number of mines asked for, or else as many as the default
+    /// density gives, if that fits.
+    fn mine_count(&self) -> Result<u32, String> {
+        if let Some(mine_count) = self.mine_count {
+            return Ok(mine_count);
+        }
+
+        let cells = self.size.0 as u64 * self.size.1 as u64;
+        let default_cells = DEFAULT_SIZE.0 as u64 * DEFAULT_SIZE.1 as u64;
+        let per_cell = DEFAULT_MINE_COUNT as u64 * self.mines_per_cell as u64;
+        u32::try_from(cells * per_cell / default_cells)
+            .map_err(|_| "too many mines for the default density; pick a number with --mines".to_string())
+    }
+
+    /// Describes the kind of board, to keep best times apart.
+    pub fn board_name(&self) -> String {
+        let geometry = &self.geometry;
+        let mut name = format!(
+            "{}x{} {} mines {:?} {:?} {:?}",
+            self.size.0,
+            self.size.1,
+            self.mine_count().expect("checked when parsing"),
+            geometry.grid,
+            geometry.topology,
+            geometry.neighborhood,
+        );
+        if self.mines_per_cell > 1 {
+            name += &format!(" {} per cell", self.mines_per_cell);
+        }
+
+        name.to_lowercase()
+    }
+
+    pub fn create_field(&self) -> Field {
+        Field::new(
+            self.size.0,
+            self.size.1,
+            self.mine_count().expect("checked when parsing"),
+            self.mines_per_cell,
+            self.geometry,
+        )
+    }
+}
+
+/// Parses sizes like `30x16`.
+fn parse_size(value: &str) -> Option<(u16, u16)> {
+    let mut parts = value.split('x');
+    let width = parts.next()?.parse().ok()?;
+    let height = parts.next()?.parse().ok()?;
+
+    if parts.next().is_some() || width == 0 || height == 0 {
+        return None;
+    }
+
+    Some((width, height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Options, String> {
+        Options::parse(args.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn keeps_the_default_density() {
+        assert_eq!(parse("").unwrap().mine_count, Some(10));
+        assert_eq!(parse("--size 16x16").unwrap().mine_count, Some(40));
+        assert_eq!(parse("--size 16x16 --mines-per-cell 3").unwrap().mine_count, Some(120));
+        assert_eq!(parse("--size 16x16 --mines 7").unwrap().mine_count, Some(7));
+    }
+
+    #[test]
+    fn rejects_default_mine_counts_that_dont_fit() {
+        let options = Options {
+            size: (65535, 65535),
+            mines_per_cell: 255,
+            ..Default::default()
+        };
+        let error = options.mine_count().unwrap_err();
+        assert!(error.contains("--mines"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_huge_boards() {
+        assert!(parse("--size 4096x4096").is_ok());
+        assert!(parse("--size 4097x4096").is_err());
+        assert!(parse("--size 65535x65535 --mines 1").is_err());
+    }
+
+    #[test]
+    fn rejects_too_many_mines() {
+        assert!(parse("--size 8x8 --mines 63").is_ok());
+        assert!(parse("--size 8x8 --mines 64").is_err());
+    }
+}
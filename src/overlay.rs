@@ -0,0 +1,57 @@
+//! The look shared by the panels drawn over the game, like the settings.
+
+use crate::font;
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, WindowCanvas};
+
+/// Size of a font pixel, before the display scale.
+const TEXT_SCALE: f32 = 3.0;
+/// In font pixels.
+pub const PADDING: u32 = 6;
+pub const LINE_HEIGHT: u32 = font::GLYPH_HEIGHT + 4;
+pub const COLUMN_GAP: u32 = 4;
+
+const SHADE: (u8, u8, u8, u8) = (0x00, 0x00, 0x00, 0x80);
+const PANEL_COLOR: (u8, u8, u8) = (0x16, 0x23, 0x3c);
+pub const BORDER_COLOR: (u8, u8, u8) = (0x44, 0x5e, 0x80);
+pub const TEXT_COLOR: (u8, u8, u8) = (0xb3, 0xc7, 0xb8);
+pub const HIGHLIGHT_COLOR: (u8, u8, u8) = (0xff, 0xff, 0xff);
+
+/// Drawable pixels per font pixel.
+pub fn text_scale(display_scale: f32) -> u32 {
+    ((TEXT_SCALE * display_scale) as u32).max(1)
+}
+
+/// Height in font pixels of `lines` lines of text.
+pub fn lines_height(lines: u32) -> u32 {
+    lines * LINE_HEIGHT - (LINE_HEIGHT - font::GLYPH_HEIGHT)
+}
+
+/// Shades `area` and draws an empty panel in its middle, big enough for
+/// `width` by `height` font pixels of content and the padding around it.
+pub fn draw_panel(canvas: &mut WindowCanvas, area: Rect, width: u32, height: u32, scale: u32) -> Rect {
+    canvas.set_blend_mode(BlendMode::Blend);
+    canvas.set_draw_color(SHADE);
+    canvas.fill_rect(area).unwrap();
+    canvas.set_blend_mode(BlendMode::None);
+
+    let panel = Rect::from_center(
+        area.center(),
+        (width + PADDING * 2) * scale,
+        (height + PADDING * 2) * scale,
+    );
+    canvas.set_draw_color(PANEL_COLOR);
+    canvas.fill_rect(panel).unwrap();
+    canvas.set_draw_color(BORDER_COLOR);
+    canvas.draw_rect(panel).unwrap();
+
+    panel
+}
+
+/// Where content `x` font pixels in on line `line` of `panel` goes.
+pub fn position(panel: Rect, x: u32, line: u32, scale: u32) -> (i32, i32) {
+    (
+        panel.x() + ((PADDING + x) * scale) as i32,
+        panel.y() + ((PADDING + line * LINE_HEIGHT) * scale) as i32,
+    )
+}
@@ -1,37 +1,51 @@
+use crate::effects::{Effects, Shape, Sprite};
+use crate::theme::Color;
+use rand::Rng;
+use sdl2::rect::Rect;
 use sdl2::render::{WindowCanvas, Texture};
 use std::cell::RefCell;
 
-const PARTICLE_SIZE: u32 = 8;
-//const PARTICLE_COLOR: (u8, u8, u8) = (0x2e, 0x53, 0x72);
-const PARTICLE_COLOR: (u8, u8, u8) = (0xff, 0xff, 0xff);
+const MAX_PARTICLE_COUNT: usize = 1024;
 
-const PARTICLE_GRAVITY: f32 = 1000.0;
-
-const MAX_PARTICLE_COUNT: usize = 256;
+/// Width of the round sprite's texture.
+const ROUND_SPRITE_SIZE: u32 = 16;
 
 #[derive(Debug)]
 pub struct Particle {
     pub pos: (f32, f32),
     pub vel: (f32, f32),
+    /// Where it was before the last update, for drawing it between the two.
+    pub prev_pos: (f32, f32),
 
     pub rot: f32,
     pub angular_vel: f32,
+    pub prev_rot: f32,
 
     pub max_lifetime: f32,
     pub lifetime: f32,
+
+    /// Which of the manager's effects it belongs to.
+    pub effect: usize,
+    /// Multiplied with the effect's colors.
+    pub color: Color,
 }
 
 impl Particle {
-    pub fn new(pos: (f32, f32), lifetime: f32) -> Self {
+    pub fn new(pos: (f32, f32), lifetime: f32, effect: usize) -> Self {
         Self {
             pos,
             vel: (0.0, 0.0),
+            prev_pos: pos,
 
             rot: 0.0,
             angular_vel: 0.0,
+            prev_rot: 0.0,
 
             max_lifetime: lifetime,
-            lifetime: lifetime,
+            lifetime,
+
+            effect,
+            color: (0xff, 0xff, 0xff),
         }
     }
 
@@ -41,12 +55,32 @@ impl Particle {
 
         self
     }
-    
-    pub fn update(&mut self, delta: f32) {
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+
+        self
+    }
+
+    pub fn with_rotation(mut self, rot: f32, angular_vel: f32) -> Self {
+        self.rot = rot;
+        self.prev_rot = rot;
+        self.angular_vel = angular_vel;
+
+        self
+    }
+
+    pub fn update(&mut self, delta: f32, gravity: f32, drag: f32) {
+        self.prev_pos = self.pos;
+        self.prev_rot = self.rot;
+
+        let keep = (-drag * delta).exp();
+        self.vel = (self.vel.0 * keep, self.vel.1 * keep);
+
         self.pos.0 += delta * self.vel.0;
-        self.pos.1 += delta * (self.vel.1 + delta*0.5*PARTICLE_GRAVITY);
+        self.pos.1 += delta * (self.vel.1 + delta*0.5*gravity);
 
-        self.vel.1 += delta * PARTICLE_GRAVITY;
+        self.vel.1 += delta * gravity;
 
         self.rot += delta * self.angular_vel;
 
@@ -57,15 +91,27 @@ impl Particle {
         self.lifetime <= 0.0
     }
 
-    pub fn alpha(&self) -> f32 {
-        self.lifetime / self.max_lifetime
+    /// How far through its life it is, from 0 to 1.
+    pub fn age(&self) -> f32 {
+        1.0 - self.lifetime / self.max_lifetime
     }
 }
 
 pub struct ParticleManager {
     particles: Vec<Particle>,
+    effects: Effects,
+    /// Drawable pixels per window coordinate.
+    scale: f32,
+    /// The theme's particle color, multiplied with the effects' own.
+    tint: Color,
+
+    square: RefCell<Texture>,
+    round: RefCell<Texture>,
+}
 
-    texture: RefCell<Texture>,
+fn multiply(a: Color, b: Color) -> Color {
+    let channel = |a: u8, b: u8| (a as u32 * b as u32 / 255) as u8;
+    (channel(a.0, b.0), channel(a.1, b.1), channel(a.2, b.2))
 }
 
 fn create_texture(canvas: &WindowCanvas) -> Texture {
@@ -78,52 +124,188 @@ fn create_texture(canvas: &WindowCanvas) -> Texture {
     ).unwrap();
     let mut texture = canvas.texture_creator()
         .create_texture_from_surface(&surface).unwrap();
-    let (r, g, b) = PARTICLE_COLOR;
-    texture.set_color_mod(r, g, b);
+    texture.set_blend_mode(sdl2::render::BlendMode::Blend);
+
+    texture
+}
+
+/// A white dot fading out towards its edge.
+fn create_round_texture(canvas: &WindowCanvas) -> Texture {
+    let size = ROUND_SPRITE_SIZE;
+    let radius = size as f32 / 2.0;
+
+    let mut pixel_data = Vec::with_capacity((size * size * 4) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            let dx = x as f32 + 0.5 - radius;
+            let dy = y as f32 + 0.5 - radius;
+            let distance = (dx * dx + dy * dy).sqrt() / radius;
+            let alpha = (1.0 - distance).clamp(0.0, 1.0).sqrt();
+            pixel_data.extend_from_slice(&[0xff, 0xff, 0xff, (alpha * 255.0) as u8]);
+        }
+    }
+
+    let surface = sdl2::surface::Surface::from_data(
+        &mut pixel_data,
+        size, size,
+        size * 4,
+        sdl2::pixels::PixelFormatEnum::RGBA32,
+    ).unwrap();
+    let mut texture = canvas.texture_creator()
+        .create_texture_from_surface(&surface).unwrap();
     texture.set_blend_mode(sdl2::render::BlendMode::Blend);
 
     texture
 }
 
 impl ParticleManager {
-    pub fn new(canvas: &WindowCanvas) -> Self {
+    pub fn new(canvas: &WindowCanvas, effects: Effects) -> Self {
         Self {
             particles: Vec::new(),
+            effects,
+            scale: 1.0,
+            tint: (0xff, 0xff, 0xff),
 
-            texture: RefCell::new(create_texture(canvas)),
+            square: RefCell::new(create_texture(canvas)),
+            round: RefCell::new(create_round_texture(canvas)),
         }
     }
 
+    /// Replaces the effects, clearing the particles of the old ones.
+    pub fn set_effects(&mut self, effects: Effects) {
+        self.effects = effects;
+        self.particles.clear();
+    }
+
     pub fn spawn(&mut self, p: Particle) {
         if self.particles.len() < MAX_PARTICLE_COUNT {
             self.particles.push(p);
         }
     }
 
-    pub fn spawn_with<F: FnMut() -> Particle>(&mut self, amt: usize, mut f: F) {
-        for _ in 0..amt {
-            self.spawn(f());
+    pub fn is_full(&self) -> bool {
+        self.particles.len() >= MAX_PARTICLE_COUNT
+    }
+
+    /// Emits the particles of the effect called `name` over `area`, in
+    /// window coordinates. Effects that aren't defined emit nothing.
+    pub fn emit(&mut self, name: &str, area: Rect) {
+        self.emit_colored(name, area, (0xff, 0xff, 0xff));
+    }
+
+    /// Like `emit`, with the particles' colors multiplied by `color`.
+    pub fn emit_colored(&mut self, name: &str, area: Rect, color: Color) {
+        let index = match self.effects.index_of(name) {
+            Some(index) => index,
+            None => return,
+        };
+        let effect = self.effects.get(index);
+
+        let mut rng = rand::thread_rng();
+        let alive = self.particles.iter().filter(|p| p.effect == index).count();
+        let count = (effect.count.sample(&mut rng).round() as usize)
+            .min(effect.limit.unwrap_or(MAX_PARTICLE_COUNT).saturating_sub(alive))
+            .min(MAX_PARTICLE_COUNT - self.particles.len());
+
+        let (x, y) = (area.x() as f32, area.y() as f32);
+        let (width, height) = (area.width() as f32, area.height() as f32);
+        for _ in 0..count {
+            let pos = match effect.shape {
+                Shape::Point => (x + width / 2.0, y + height / 2.0),
+                Shape::Area => (x + rng.gen::<f32>() * width, y + rng.gen::<f32>() * height),
+                Shape::Circle => {
+                    let angle = rng.gen_range(0.0, std::f32::consts::TAU);
+                    // spread evenly over the circle, rather than bunched
+                    // in the middle
+                    let distance = rng.gen::<f32>().sqrt() / 2.0;
+                    (
+                        x + width * (0.5 + angle.sin() * distance),
+                        y + height * (0.5 - angle.cos() * distance),
+                    )
+                }
+                Shape::Top => (x + rng.gen::<f32>() * width, y),
+            };
+
+            let spread = effect.spread / 2.0;
+            let direction = if spread > 0.0 {
+                effect.direction + rng.gen_range(-spread, spread)
+            } else {
+                effect.direction
+            };
+
+            let color = if effect.palette.is_empty() {
+                color
+            } else {
+                multiply(color, effect.palette[rng.gen_range(0, effect.palette.len())])
+            };
+            let particle = Particle::new(pos, effect.lifetime.sample(&mut rng).max(0.01), index)
+                .with_direction(direction, effect.speed.sample(&mut rng) * self.scale)
+                .with_rotation(effect.rotation.sample(&mut rng), effect.spin.sample(&mut rng))
+                .with_color(color);
+            self.particles.push(particle);
+        }
+    }
+
+    /// Keeps particles attached to the field when the camera moves: `map`
+    /// takes their old window position to the new one, and `scale` is how
+    /// much the field grew.
+    pub fn transform<F: Fn((f32, f32)) -> (f32, f32)>(&mut self, map: F, scale: f32) {
+        for p in &mut self.particles {
+            p.pos = map(p.pos);
+            p.prev_pos = map(p.prev_pos);
+            p.vel = (p.vel.0 * scale, p.vel.1 * scale);
         }
     }
 
+    /// Sizes, speeds and gravity are multiplied by `scale`, so particles
+    /// look the same on high density displays. Doesn't affect existing
+    /// particles' velocities; see `transform`.
+    pub fn set_scale(&mut self, scale: f32) {
+        self.scale = scale;
+    }
+
+    pub fn set_color(&mut self, color: Color) {
+        self.tint = color;
+    }
+
     pub fn update(&mut self, delta: f32) {
-        self.particles.iter_mut().for_each(|p| p.update(delta));
+        for p in &mut self.particles {
+            let effect = self.effects.get(p.effect);
+            p.update(delta, effect.gravity * self.scale, effect.drag);
+        }
         self.particles.retain(|p| !p.is_dead());
     }
 
-    pub fn render(&self, canvas: &mut WindowCanvas) {
+    /// Draws the particles `blend` of the way from where they were before
+    /// the last update to where they are now, moved by `offset`.
+    pub fn render(&self, canvas: &mut WindowCanvas, offset: (i32, i32), blend: f32) {
         for p in &self.particles {
-            let x = p.pos.0 as i32 - PARTICLE_SIZE as i32/2;
-            let y = p.pos.1 as i32 - PARTICLE_SIZE as i32/2;
+            let effect = self.effects.get(p.effect);
+            let age = p.age();
+
+            let width = (effect.size_at(age) * self.scale).round().max(1.0) as u32;
+            let height = (width as f32 * effect.aspect).round().max(1.0) as u32;
+            let pos = (
+                p.prev_pos.0 + (p.pos.0 - p.prev_pos.0) * blend,
+                p.prev_pos.1 + (p.pos.1 - p.prev_pos.1) * blend,
+            );
+            let rot = p.prev_rot + (p.rot - p.prev_rot) * blend;
+            let x = pos.0 as i32 - width as i32/2 + offset.0;
+            let y = pos.1 as i32 - height as i32/2 + offset.1;
 
-            let mut texture = self.texture.borrow_mut();
-            texture.set_alpha_mod((p.alpha() * 255.0) as u8);
+            let mut texture = match effect.sprite {
+                Sprite::Square => self.square.borrow_mut(),
+                Sprite::Round => self.round.borrow_mut(),
+            };
+            let (r, g, b) = multiply(multiply(effect.color_at(age), p.color), self.tint);
+            texture.set_color_mod(r, g, b);
+            texture.set_alpha_mod((effect.alpha_at(age) * 255.0) as u8);
 
             canvas.copy_ex(
-                &*texture,
+                &texture,
                 None,
-                Some((x, y, PARTICLE_SIZE, PARTICLE_SIZE).into()),
-                p.rot as f64 / std::f64::consts::TAU * 360.0, None,
+                Some((x, y, width, height).into()),
+                rot as f64 / std::f64::consts::TAU * 360.0, None,
                 false, false,
             ).unwrap();
         }
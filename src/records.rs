@@ -0,0 +1,134 @@
+//! Best times, kept per kind of board in a `config` file.
+
+use crate::config;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+const RECORDS_FILE: &str = "best_times.txt";
+
+#[derive(Debug, Default)]
+pub struct Records {
+    /// By board description, see `Options::board_name`.
+    best_times: HashMap<String, Duration>,
+    /// Where they're saved; without a config directory they aren't.
+    path: Option<PathBuf>,
+}
+
+impl Records {
+    pub fn load() -> Self {
+        Records::load_from(records_path())
+    }
+
+    fn load_from(path: Option<PathBuf>) -> Self {
+        let mut records = Records {
+            best_times: HashMap::new(),
+            path: path.clone(),
+        };
+
+        let path = match path {
+            Some(path) => path,
+            None => return records,
+        };
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            // nothing won yet
+            Err(_) => return records,
+        };
+
+        for (line, entry) in config::parse(&text) {
+            let result = entry.and_then(|(board, time)| {
+                let time = time
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|time| time.is_finite() && *time >= 0.0)
+                    .ok_or_else(|| format!("invalid time {}", time))?;
+                records.best_times.insert(board.into(), Duration::from_secs_f64(time));
+                Ok(())
+            });
+            if let Err(e) = result {
+                eprintln!("{}: line {}: {}", path.display(), line, e);
+            }
+        }
+
+        records
+    }
+
+    pub fn best(&self, board: &str) -> Option<Duration> {
+        self.best_times.get(board).copied()
+    }
+
+    /// Records a win on `board`, returning whether it beat the best time.
+    pub fn submit(&mut self, board: &str, time: Duration) -> bool {
+        match self.best_times.get(board) {
+            Some(&best) if best <= time => return false,
+            _ => (),
+        }
+
+        self.best_times.insert(board.into(), time);
+        self.save();
+        true
+    }
+
+    fn save(&self) {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return,
+        };
+
+        let mut boards: Vec<_> = self.best_times.iter().collect();
+        boards.sort();
+        let text: String = boards
+            .into_iter()
+            .map(|(board, time)| format!("{} = {:.3}\n", board, time.as_secs_f64()))
+            .collect();
+
+        let result = path
+            .parent()
+            .map(std::fs::create_dir_all)
+            .unwrap_or(Ok(()))
+            .and_then(|_| std::fs::write(path, text));
+        if let Err(e) = result {
+            eprintln!("can't save best times to {}: {}", path.display(), e);
+        }
+    }
+}
+
+fn records_path() -> Option<PathBuf> {
+    config::dir().map(|dir| dir.join(RECORDS_FILE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_best_time() {
+        let mut records = Records::default();
+        assert_eq!(records.best("9x9"), None);
+
+        assert!(records.submit("9x9", Duration::from_secs(30)));
+        assert!(!records.submit("9x9", Duration::from_secs(40)));
+        assert!(!records.submit("9x9", Duration::from_secs(30)));
+        assert!(records.submit("9x9", Duration::from_secs(20)));
+        assert_eq!(records.best("9x9"), Some(Duration::from_secs(20)));
+        assert_eq!(records.best("16x16"), None);
+    }
+
+    #[test]
+    fn saves_and_loads() {
+        let dir = std::env::temp_dir().join(format!("sdl2-minesweeper-records-{}", std::process::id()));
+        let path = dir.join(RECORDS_FILE);
+
+        let mut records = Records::load_from(Some(path.clone()));
+        records.submit("9x9 10 mines", Duration::from_millis(12_345));
+        records.submit("hex 16x16", Duration::from_secs(90));
+        std::fs::write(&path, std::fs::read_to_string(&path).unwrap() + "broken = -1\n").unwrap();
+
+        let loaded = Records::load_from(Some(path));
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(loaded.best("9x9 10 mines"), Some(Duration::from_millis(12_345)));
+        assert_eq!(loaded.best("hex 16x16"), Some(Duration::from_secs(90)));
+        assert_eq!(loaded.best("broken"), None);
+    }
+}
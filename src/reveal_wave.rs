@@ -0,0 +1,255 @@
+//! Revealed cells flipping open one after another, spreading out from the
+//! click the way the flood fill found them. It's only for show: the field
+//! has already revealed them all.
+
+use crate::field::Field;
+
+/// Delay between each step of the flood fill, in seconds, and the least
+/// time between the ticks it makes.
+const WAVE_STEP: f32 = 0.03;
+/// Big floods are sped up to finish within this many seconds.
+const MAX_WAVE_TIME: f32 = 1.0;
+/// How long a single cell takes to flip over.
+const FLIP_TIME: f32 = 0.12;
+
+/// The cells of one click, a step at a time.
+#[derive(Debug)]
+struct Wave {
+    steps: Vec<Vec<(u16, u16)>>,
+    /// When the first step starts flipping, by the clock of `RevealWave`.
+    started: f32,
+    step_time: f32,
+    /// The next step to start flipping, and the first that hasn't finished.
+    next: usize,
+    done: usize,
+}
+
+impl Wave {
+    fn step_start(&self, step: usize) -> f32 {
+        self.started + step as f32 * self.step_time
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct RevealWave {
+    /// Seconds since the waves started, from when there were none.
+    time: f32,
+    waves: Vec<Wave>,
+    /// When each cell of the board starts flipping, by index, so that
+    /// drawing doesn't search the waves. Cells that aren't part of one are
+    /// infinitely long done. Made with the first wave.
+    flip_starts: Vec<f32>,
+    width: usize,
+    /// The furthest step that has started flipping since the last tick, and
+    /// how long until the next tick can sound.
+    untold: Option<u32>,
+    tick_wait: f32,
+}
+
+impl RevealWave {
+    /// Starts the cells flipping a step at a time, as `Field::reveal`
+    /// groups them.
+    pub fn start(&mut self, field: &Field, steps: Vec<Vec<(u16, u16)>>) {
+        let (width, height) = field.size();
+        let cell_count = width as usize * height as usize;
+        if self.flip_starts.len() != cell_count {
+            self.flip_starts = vec![f32::NEG_INFINITY; cell_count];
+            self.width = width as usize;
+        }
+
+        let last = steps.len().saturating_sub(1);
+        let step_time = if last as f32 * WAVE_STEP > MAX_WAVE_TIME {
+            MAX_WAVE_TIME / last as f32
+        } else {
+            WAVE_STEP
+        };
+
+        let wave = Wave {
+            steps,
+            started: self.time,
+            step_time,
+            next: 0,
+            done: 0,
+        };
+        for (step, cells) in wave.steps.iter().enumerate() {
+            let start = wave.step_start(step);
+            for &(x, y) in cells {
+                self.flip_starts[x as usize + y as usize * self.width] = start;
+            }
+        }
+        self.waves.push(wave);
+    }
+
+    /// Advances the waves, returning the cells that start flipping and how
+    /// many steps from the click they are. Only the steps starting or
+    /// finishing are looked at.
+    pub fn update(&mut self, delta: f32) -> Vec<((u16, u16), u32)> {
+        self.time += delta;
+
+        let mut started = Vec::new();
+        for wave in &mut self.waves {
+            while wave.next < wave.steps.len() && wave.step_start(wave.next) <= self.time {
+                let step = wave.next as u32;
+                started.extend(wave.steps[wave.next].iter().map(|&pos| (pos, step)));
+                wave.next += 1;
+            }
+
+            while wave.done < wave.next && wave.step_start(wave.done) + FLIP_TIME <= self.time {
+                for &(x, y) in &wave.steps[wave.done] {
+                    self.flip_starts[x as usize + y as usize * self.width] = f32::NEG_INFINITY;
+                }
+                wave.done += 1;
+            }
+        }
+        self.waves.retain(|wave| wave.done < wave.steps.len());
+        // so the clock never runs long enough to lose precision
+        if self.waves.is_empty() {
+            self.time = 0.0;
+        }
+
+        // the click itself already dug
+        let furthest = started.iter().map(|&(_, step)| step).filter(|&step| step > 0).max();
+        self.untold = self.untold.max(furthest);
+        self.tick_wait -= delta;
+
+        started
+    }
+
+    /// The step to sound a tick for, if the wave has come further since the
+    /// last one. Sped up waves would otherwise tick every update.
+    pub fn tick(&mut self) -> Option<u32> {
+        if self.tick_wait > 0.0 {
+            return None;
+        }
+
+        let step = self.untold.take()?;
+        self.tick_wait = WAVE_STEP;
+        Some(step)
+    }
+
+    /// How far over the cell at `pos` has flipped, from 0 to 1, if it's
+    /// still part of a wave.
+    pub fn progress(&self, (x, y): (u16, u16)) -> Option<f32> {
+        let start = *self.flip_starts.get(x as usize + y as usize * self.width)?;
+        let flipped = (self.time - start) / FLIP_TIME;
+        if flipped < 1.0 {
+            Some(flipped.max(0.0))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::Geometry;
+
+    fn row(length: u16) -> Field {
+        Field::new(length, 1, 0, 1, Geometry::default())
+    }
+
+    /// Runs the wave to the end, returning when each cell started and how
+    /// many steps from the click it was.
+    fn run(wave: &mut RevealWave) -> Vec<(f32, (u16, u16), u32)> {
+        let delta = 0.001;
+        let mut started = Vec::new();
+        for tick in 0..2000 {
+            for (pos, step) in wave.update(delta) {
+                started.push((tick as f32 * delta, pos, step));
+            }
+        }
+        started.sort_by(|a, b| a.0.total_cmp(&b.0));
+        started
+    }
+
+    /// The cells of a row of `length`, by their steps from `origin`.
+    fn steps_along(length: u16, origin: u16) -> Vec<Vec<(u16, u16)>> {
+        let mut steps = vec![Vec::new(); (length - origin).max(origin + 1) as usize];
+        for x in 0..length {
+            steps[(x as i32 - origin as i32).unsigned_abs() as usize].push((x, 0));
+        }
+        steps
+    }
+
+    #[test]
+    fn flips_cells_in_steps_from_the_click() {
+        let mut wave = RevealWave::default();
+        wave.start(&row(5), steps_along(5, 2));
+
+        let started = run(&mut wave);
+        assert_eq!(started.len(), 5);
+        for &(time, (x, _), step) in &started {
+            assert_eq!(step, (x as i32 - 2).unsigned_abs());
+            assert!((time - step as f32 * WAVE_STEP).abs() < 0.002, "{:?}", started);
+        }
+    }
+
+    #[test]
+    fn speeds_up_big_floods() {
+        let mut wave = RevealWave::default();
+        wave.start(&row(100), steps_along(100, 0));
+
+        let started = run(&mut wave);
+        assert_eq!(started.len(), 100);
+        assert!(started.last().unwrap().0 <= MAX_WAVE_TIME);
+    }
+
+    #[test]
+    fn starts_each_cell_once() {
+        let mut wave = RevealWave::default();
+        wave.start(&row(5), vec![vec![(0, 0)], vec![], vec![(2, 0), (4, 0)]]);
+
+        let started = run(&mut wave);
+        let steps: Vec<_> = started.iter().map(|&(_, pos, step)| (pos, step)).collect();
+        assert_eq!(steps, vec![((0, 0), 0), ((2, 0), 2), ((4, 0), 2)]);
+    }
+
+    #[test]
+    fn runs_overlapping_waves_on_their_own_time() {
+        let mut wave = RevealWave::default();
+        wave.start(&row(10), vec![vec![(0, 0)], vec![(1, 0)]]);
+        wave.update(WAVE_STEP / 2.0);
+        wave.start(&row(10), vec![vec![(5, 0)], vec![(6, 0)]]);
+
+        let started = run(&mut wave);
+        let times: Vec<_> = started.iter().map(|&(time, _, _)| time).collect();
+        assert_eq!(started.iter().map(|&(_, pos, _)| pos).collect::<Vec<_>>(), vec![(5, 0), (1, 0), (6, 0)]);
+        assert!((times[2] - times[0] - WAVE_STEP).abs() < 0.002, "{:?}", started);
+        assert!(wave.progress((6, 0)).is_none());
+    }
+
+    #[test]
+    fn ticks_at_most_once_a_step() {
+        let mut wave = RevealWave::default();
+        wave.start(&row(200), steps_along(200, 0));
+
+        let delta = 0.001;
+        let mut ticks = Vec::new();
+        for tick in 0..2000 {
+            wave.update(delta);
+            if let Some(step) = wave.tick() {
+                ticks.push((tick as f32 * delta, step));
+            }
+        }
+
+        assert!(ticks.windows(2).all(|pair| pair[1].0 - pair[0].0 >= WAVE_STEP - delta / 2.0));
+        assert!(ticks.windows(2).all(|pair| pair[0].1 < pair[1].1));
+        assert_eq!(ticks.last().unwrap().1, 199);
+        assert!(ticks.len() as f32 <= MAX_WAVE_TIME / WAVE_STEP + 2.0);
+    }
+
+    #[test]
+    fn tracks_progress_until_flipped() {
+        let mut wave = RevealWave::default();
+        wave.start(&row(2), steps_along(2, 0));
+
+        assert_eq!(wave.progress((1, 0)), Some(0.0));
+        wave.update(WAVE_STEP + FLIP_TIME / 2.0);
+        let progress = wave.progress((1, 0)).unwrap();
+        assert!((progress - 0.5).abs() < 0.01, "{}", progress);
+        wave.update(FLIP_TIME);
+        assert_eq!(wave.progress((1, 0)), None);
+        assert_eq!(wave.progress((0, 0)), None);
+    }
+}
@@ -0,0 +1,233 @@
+//! A fixed size queue from one thread to another, for talking to the audio
+//! callback. Pushing and popping neither lock nor allocate; the slots are
+//! allocated once, when the queue is made.
+
+use std::cell::{Cell, UnsafeCell};
+use std::marker::PhantomData;
+use std::mem::MaybeUninit;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+#[derive(Debug)]
+struct Shared<T> {
+    slots: Box<[UnsafeCell<MaybeUninit<T>>]>,
+    /// How many values have ever been popped and pushed. They wrap around,
+    /// and the slot of each is its count modulo the number of slots.
+    popped: AtomicUsize,
+    pushed: AtomicUsize,
+    /// Whether either end has been dropped.
+    producer_dropped: AtomicBool,
+    consumer_dropped: AtomicBool,
+}
+
+// values pushed on one thread are popped on the other
+unsafe impl<T: Send> Send for Shared<T> {}
+unsafe impl<T: Send> Sync for Shared<T> {}
+
+impl<T> Shared<T> {
+    fn slot(&self, count: usize) -> *mut MaybeUninit<T> {
+        // the number of slots is a power of two, so this carries on
+        // smoothly when the counts wrap around
+        self.slots[count & (self.slots.len() - 1)].get()
+    }
+}
+
+impl<T> Drop for Shared<T> {
+    fn drop(&mut self) {
+        let pushed = *self.pushed.get_mut();
+        let mut popped = *self.popped.get_mut();
+        while popped != pushed {
+            unsafe { (*self.slot(popped)).as_mut_ptr().drop_in_place() };
+            popped = popped.wrapping_add(1);
+        }
+    }
+}
+
+/// The pushing end. It can move to another thread, but not be shared, so
+/// that there's only ever one thread pushing.
+#[derive(Debug)]
+pub struct Producer<T> {
+    shared: Arc<Shared<T>>,
+    not_sync: PhantomData<Cell<()>>,
+}
+
+/// The popping end, which likewise stays with one thread.
+#[derive(Debug)]
+pub struct Consumer<T> {
+    shared: Arc<Shared<T>>,
+    not_sync: PhantomData<Cell<()>>,
+}
+
+/// Makes a queue that holds at least `capacity` values.
+pub fn ring<T>(capacity: usize) -> (Producer<T>, Consumer<T>) {
+    let slots = (0..capacity.max(1).next_power_of_two())
+        .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
+        .collect();
+    let shared = Arc::new(Shared {
+        slots,
+        popped: AtomicUsize::new(0),
+        pushed: AtomicUsize::new(0),
+        producer_dropped: AtomicBool::new(false),
+        consumer_dropped: AtomicBool::new(false),
+    });
+
+    let producer = Producer {
+        shared: shared.clone(),
+        not_sync: PhantomData,
+    };
+    let consumer = Consumer {
+        shared,
+        not_sync: PhantomData,
+    };
+    (producer, consumer)
+}
+
+impl<T> Producer<T> {
+    /// Queues `value`, or hands it back if the queue is full.
+    pub fn push(&self, value: T) -> Result<(), T> {
+        let shared = &*self.shared;
+        let pushed = shared.pushed.load(Ordering::Relaxed);
+        let popped = shared.popped.load(Ordering::Acquire);
+        if pushed.wrapping_sub(popped) == shared.slots.len() {
+            return Err(value);
+        }
+
+        // the consumer doesn't touch the slot until the count says it's
+        // been written
+        unsafe { (*shared.slot(pushed)).as_mut_ptr().write(value) };
+        shared.pushed.store(pushed.wrapping_add(1), Ordering::Release);
+        Ok(())
+    }
+
+    /// Whether the consumer is gone, so nothing pushed will be popped.
+    pub fn is_abandoned(&self) -> bool {
+        self.shared.consumer_dropped.load(Ordering::Acquire)
+    }
+}
+
+impl<T> Drop for Producer<T> {
+    fn drop(&mut self) {
+        self.shared.producer_dropped.store(true, Ordering::Release);
+    }
+}
+
+impl<T> Consumer<T> {
+    /// Takes the oldest value queued, if there is one.
+    pub fn pop(&self) -> Option<T> {
+        let shared = &*self.shared;
+        let popped = shared.popped.load(Ordering::Relaxed);
+        let pushed = shared.pushed.load(Ordering::Acquire);
+        if popped == pushed {
+            return None;
+        }
+
+        // likewise the producer leaves the slot alone until it's popped
+        let value = unsafe { (*shared.slot(popped)).as_ptr().read() };
+        shared.popped.store(popped.wrapping_add(1), Ordering::Release);
+        Some(value)
+    }
+
+    /// Whether the producer is gone, so once this is empty it stays empty.
+    /// Everything it pushed can still be popped.
+    pub fn is_abandoned(&self) -> bool {
+        self.shared.producer_dropped.load(Ordering::Acquire)
+    }
+}
+
+impl<T> Drop for Consumer<T> {
+    fn drop(&mut self) {
+        self.shared.consumer_dropped.store(true, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pops_in_order() {
+        let (producer, consumer) = ring(4);
+        for i in 0..3 {
+            producer.push(i).unwrap();
+        }
+        assert_eq!(consumer.pop(), Some(0));
+        assert_eq!(consumer.pop(), Some(1));
+        assert_eq!(consumer.pop(), Some(2));
+        assert_eq!(consumer.pop(), None);
+    }
+
+    #[test]
+    fn hands_values_back_when_full() {
+        let (producer, consumer) = ring(3);
+        for i in 0..4 {
+            producer.push(i).unwrap();
+        }
+        assert_eq!(producer.push(4), Err(4));
+        assert_eq!(consumer.pop(), Some(0));
+        assert_eq!(producer.push(4), Ok(()));
+    }
+
+    #[test]
+    fn wraps_around() {
+        let (producer, consumer) = ring(2);
+        for i in 0..10 {
+            producer.push(i).unwrap();
+            assert_eq!(consumer.pop(), Some(i));
+        }
+    }
+
+    #[test]
+    fn drops_what_was_never_popped() {
+        let value = Arc::new(());
+        let (producer, consumer) = ring(4);
+        producer.push(value.clone()).unwrap();
+        producer.push(value.clone()).unwrap();
+        consumer.pop();
+        assert_eq!(Arc::strong_count(&value), 2);
+
+        drop((producer, consumer));
+        assert_eq!(Arc::strong_count(&value), 1);
+    }
+
+    #[test]
+    fn tells_when_the_other_end_is_gone() {
+        let (producer, consumer) = ring(4);
+        assert!(!producer.is_abandoned());
+        assert!(!consumer.is_abandoned());
+
+        producer.push(1).unwrap();
+        drop(producer);
+        assert!(consumer.is_abandoned());
+        assert_eq!(consumer.pop(), Some(1));
+
+        let (producer, consumer) = ring::<i32>(4);
+        drop(consumer);
+        assert!(producer.is_abandoned());
+    }
+
+    #[test]
+    fn passes_values_between_threads() {
+        let (producer, consumer) = ring(8);
+        let pusher = std::thread::spawn(move || {
+            for i in 0..10_000 {
+                let mut value = i;
+                while let Err(back) = producer.push(value) {
+                    value = back;
+                    std::thread::yield_now();
+                }
+            }
+        });
+
+        let mut expected = 0;
+        while expected < 10_000 {
+            match consumer.pop() {
+                Some(value) => {
+                    assert_eq!(value, expected);
+                    expected += 1;
+                }
+                None => std::thread::yield_now(),
+            }
+        }
+        pusher.join().unwrap();
+    }
+}
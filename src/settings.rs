@@ -0,0 +1,101 @@
+//! Preferences kept between runs, in a `config` file.
+
+use crate::audio::Volume;
+use crate::config;
+use std::path::PathBuf;
+
+const SETTINGS_FILE: &str = "settings.txt";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Settings {
+    pub volume: Volume,
+    /// Whether moving the pointer across cells ticks.
+    pub hover_ticks: bool,
+    /// Whether the timer ticks every second.
+    pub timer_ticks: bool,
+}
+
+impl Settings {
+    /// Loads the saved settings, using the defaults for any that are
+    /// missing or invalid.
+    pub fn load() -> Self {
+        let mut settings = Settings::default();
+
+        let path = match settings_path() {
+            Some(path) => path,
+            None => return settings,
+        };
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            // nothing saved yet
+            Err(_) => return settings,
+        };
+
+        for (line, entry) in config::parse(&text) {
+            let result = entry.and_then(|(key, value)| settings.set(key, value));
+            if let Err(e) = result {
+                eprintln!("{}: line {}: {}", path.display(), line, e);
+            }
+        }
+
+        settings
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let level = || {
+            value
+                .parse::<f32>()
+                .ok()
+                .filter(|level| (0.0..=1.0).contains(level))
+                .ok_or_else(|| format!("invalid volume {}", value))
+        };
+
+        match key {
+            "master_volume" => self.volume.master = level()?,
+            "effects_volume" => self.volume.effects = level()?,
+            "music_volume" => self.volume.music = level()?,
+            "muted" => self.volume.muted = parse_bool(value)?,
+            "hover_ticks" => self.hover_ticks = parse_bool(value)?,
+            "timer_ticks" => self.timer_ticks = parse_bool(value)?,
+            _ => return Err(format!("unknown key {}", key)),
+        }
+
+        Ok(())
+    }
+
+    pub fn save(&self) {
+        let path = match settings_path() {
+            Some(path) => path,
+            None => return,
+        };
+
+        let text = format!(
+            "master_volume = {}\neffects_volume = {}\nmusic_volume = {}\nmuted = {}\nhover_ticks = {}\ntimer_ticks = {}\n",
+            self.volume.master,
+            self.volume.effects,
+            self.volume.music,
+            self.volume.muted,
+            self.hover_ticks,
+            self.timer_ticks,
+        );
+
+        let result = path
+            .parent()
+            .map(std::fs::create_dir_all)
+            .unwrap_or(Ok(()))
+            .and_then(|_| std::fs::write(&path, text));
+        if let Err(e) = result {
+            eprintln!("can't save settings to {}: {}", path.display(), e);
+        }
+    }
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    value
+        .parse()
+        .map_err(|_| format!("expected true or false, not {}", value))
+}
+
+fn settings_path() -> Option<PathBuf> {
+    config::dir().map(|dir| dir.join(SETTINGS_FILE))
+}
@@ -0,0 +1,137 @@
+//! An overlay for adjusting the volume and sounds, opened with Escape.
+
+use crate::font;
+use crate::overlay::{self, BORDER_COLOR, COLUMN_GAP, HIGHLIGHT_COLOR, TEXT_COLOR};
+use crate::settings::Settings;
+use sdl2::keyboard::Keycode;
+use sdl2::rect::Rect;
+use sdl2::render::WindowCanvas;
+
+const TITLE: &str = "SETTINGS";
+const HINT: &str = "ESC TO CLOSE";
+const ROWS: [&str; 6] = ["MASTER", "EFFECTS", "MUSIC", "MUTE", "HOVER TICKS", "TIMER TICKS"];
+/// Rows from here on are switched on and off rather than set.
+const FIRST_SWITCH_ROW: usize = 3;
+
+/// How much one key press changes a volume.
+const VOLUME_STEP: f32 = 0.1;
+
+/// In font pixels.
+const BAR_WIDTH: u32 = 40;
+
+const BAR_BACKGROUND: (u8, u8, u8) = (0x29, 0x51, 0x6e);
+const BAR_COLOR: (u8, u8, u8) = (0x6d, 0xab, 0x33);
+
+#[derive(Debug, Default)]
+pub struct SettingsScreen {
+    open: bool,
+    selected: usize,
+}
+
+impl SettingsScreen {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+    }
+
+    /// Handles a key press while open. Returns whether `settings` changed.
+    pub fn key_down(&mut self, keycode: Keycode, settings: &mut Settings) -> bool {
+        let change = match keycode {
+            Keycode::Up | Keycode::W => {
+                self.selected = (self.selected + ROWS.len() - 1) % ROWS.len();
+                return false;
+            }
+            Keycode::Down | Keycode::S => {
+                self.selected = (self.selected + 1) % ROWS.len();
+                return false;
+            }
+            Keycode::Return | Keycode::Space if self.selected >= FIRST_SWITCH_ROW => {
+                self.toggle_selected(settings);
+                return true;
+            }
+            Keycode::Left | Keycode::A => -VOLUME_STEP,
+            Keycode::Right | Keycode::D => VOLUME_STEP,
+            _ => return false,
+        };
+
+        let volume = &mut settings.volume;
+        let level = match self.selected {
+            0 => &mut volume.master,
+            1 => &mut volume.effects,
+            2 => &mut volume.music,
+            _ => {
+                self.toggle_selected(settings);
+                return true;
+            }
+        };
+
+        // rounded so repeated steps don't drift
+        *level = ((*level + change) * 10.0).round().clamp(0.0, 10.0) / 10.0;
+        true
+    }
+
+    fn toggle_selected(&self, settings: &mut Settings) {
+        match self.selected {
+            3 => settings.volume.muted = !settings.volume.muted,
+            4 => settings.hover_ticks = !settings.hover_ticks,
+            _ => settings.timer_ticks = !settings.timer_ticks,
+        }
+    }
+
+    /// Draws the screen centered in `area`.
+    pub fn render(&self, canvas: &mut WindowCanvas, settings: &Settings, area: Rect, display_scale: f32) {
+        let scale = overlay::text_scale(display_scale);
+
+        let label_width = ROWS.iter().map(|row| font::text_width(row)).max().unwrap();
+        let value_width = font::text_width("100%");
+        let width = label_width + COLUMN_GAP + BAR_WIDTH + COLUMN_GAP + value_width;
+        let lines = ROWS.len() as u32 + 4;
+        let panel = overlay::draw_panel(canvas, area, width, overlay::lines_height(lines), scale);
+        let at = |x: u32, line: u32| overlay::position(panel, x, line, scale);
+
+        canvas.set_draw_color(HIGHLIGHT_COLOR);
+        font::draw(canvas, TITLE, at((width - font::text_width(TITLE)) / 2, 0), scale);
+
+        let volume = &settings.volume;
+        let levels = [volume.master, volume.effects, volume.music];
+        for (i, row) in ROWS.iter().enumerate() {
+            let line = i as u32 + 2;
+            let text_color = if i == self.selected { HIGHLIGHT_COLOR } else { TEXT_COLOR };
+            canvas.set_draw_color(text_color);
+            font::draw(canvas, row, at(0, line), scale);
+
+            let bar_x = label_width + COLUMN_GAP;
+            if i >= FIRST_SWITCH_ROW {
+                let on = match i {
+                    3 => volume.muted,
+                    4 => settings.hover_ticks,
+                    _ => settings.timer_ticks,
+                };
+                font::draw(canvas, if on { "ON" } else { "OFF" }, at(bar_x, line), scale);
+                continue;
+            }
+
+            let level = levels[i];
+            let (x, y) = at(bar_x, line);
+            let bar = Rect::new(x, y, BAR_WIDTH * scale, font::GLYPH_HEIGHT * scale);
+            canvas.set_draw_color(BAR_BACKGROUND);
+            canvas.fill_rect(bar).unwrap();
+            let filled = (bar.width() as f32 * level) as u32;
+            if filled > 0 {
+                canvas.set_draw_color(if volume.muted { BORDER_COLOR } else { BAR_COLOR });
+                canvas.fill_rect(Rect::new(x, y, filled, bar.height())).unwrap();
+            }
+
+            canvas.set_draw_color(text_color);
+            let value = format!("{}%", (level * 100.0).round());
+            font::draw(canvas, &value, at(bar_x + BAR_WIDTH + COLUMN_GAP, line), scale);
+        }
+
+        canvas.set_draw_color(TEXT_COLOR);
+        let hint_x = (width - font::text_width(HINT)) / 2;
+        font::draw(canvas, HINT, at(hint_x, lines - 1), scale);
+    }
+}
@@ -1,39 +1,185 @@
-use lazy_static::lazy_static;
+use crate::assets::Assets;
+use crate::audio::Voice;
+use crate::sound::{self, Sound};
+use crate::synth::{Preset, Presets};
+use rand::Rng;
 
-const CHANNEL_COUNT: u8 = 1;
+/// Where the synthesized sounds are defined.
+pub const PRESETS: &str = "src/sfx/presets.txt";
+
+/// Floods are sorted by size into this many steps, each a doubling of the
+/// cells uncovered, starting with the smallest of two or three cells.
+const FLOOD_STEPS: usize = 8;
+/// How much lower, in octaves, and how much longer each step sounds.
+const FLOOD_PITCH_STEP: f32 = 0.15;
+const FLOOD_LENGTH_STEP: f32 = 0.25;
+
+/// Things in the game that make a sound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SoundEvent {
+    Dig,
+    Boom,
+    Flag,
+    Unflag,
+    Chord,
+    /// A click that did nothing.
+    WastedClick,
+    Win,
+    /// Played instead of `Win` when the time is the best yet.
+    BestTime,
+    /// The pointer moving onto another cell.
+    HoverTick,
+    /// Each second the timer counts.
+    TimerTick,
+    /// Several cells uncovered at once, sounding deeper the more there are.
+    Flood,
+}
+
+/// How each event sounds: the name of the preset that synthesizes it, the
+/// samples it picks between otherwise, how far its pitch randomly strays
+/// either way, and how loud it is.
+const CATALOG: &[(SoundEvent, &str, &[&str], f32, f32)] = &[
+    (SoundEvent::Dig, "dig", &["src/sfx/dig.ogg"], 0.08, 1.0),
+    (SoundEvent::Boom, "boom", &["src/sfx/boom.ogg"], 0.05, 1.0),
+    (SoundEvent::Flag, "flag", &["src/sfx/flag_1.wav", "src/sfx/flag_2.wav"], 0.05, 0.6),
+    (SoundEvent::Unflag, "unflag", &["src/sfx/unflag.wav"], 0.05, 0.5),
+    (SoundEvent::Chord, "chord", &["src/sfx/chord.wav"], 0.03, 0.5),
+    (SoundEvent::WastedClick, "wasted", &["src/sfx/wasted.wav"], 0.1, 0.4),
+    (SoundEvent::Win, "win", &["src/sfx/win.wav"], 0.0, 0.8),
+    (SoundEvent::BestTime, "best_time", &["src/sfx/best_time.wav"], 0.0, 0.8),
+    (SoundEvent::HoverTick, "hover", &["src/sfx/hover.wav"], 0.15, 0.15),
+    (SoundEvent::TimerTick, "timer", &[], 0.0, 0.2),
+    (SoundEvent::Flood, "flood", &[], 0.03, 0.5),
+];
+
+struct EventSounds {
+    event: SoundEvent,
+    variants: Vec<Sound>,
+    pitch_variation: f32,
+    gain: f32,
+}
 
 pub struct SoundEffects {
-    pub dig: Vec<i16>,
-    pub boom: Vec<i16>,
+    events: Vec<EventSounds>,
 }
 
 impl SoundEffects {
-    pub fn new() -> Self {
-        Self {
-            dig: SoundEffects::load(include_bytes!("sfx/dig.ogg")),
-            boom: SoundEffects::load(include_bytes!("sfx/boom.ogg")),
-        }
+    /// Loads and synthesizes the sound effects for a device playing at
+    /// `sample_rate`. Events with a preset are synthesized, and the rest
+    /// use their samples.
+    pub fn new(assets: &Assets, sample_rate: u32) -> Self {
+        let presets = assets.try_load(PRESETS, Presets::parse).unwrap_or_else(|e| {
+            eprintln!("can't load the sound presets, only samples will play: {}", e);
+            Presets::default()
+        });
+
+        let events = CATALOG
+            .iter()
+            .map(|&(event, preset_name, names, pitch_variation, gain)| {
+                let mut variants: Vec<Sound> = match presets.get(preset_name) {
+                    Some(preset) if event == SoundEvent::Flood => (0..FLOOD_STEPS)
+                        .map(|step| flood_preset(preset, step).render(sample_rate))
+                        .collect(),
+                    Some(preset) => vec![preset.render(sample_rate)],
+                    None => names
+                        .iter()
+                        .map(|name| SoundEffects::load(assets, name, sample_rate))
+                        .collect(),
+                };
+                if variants.is_empty() {
+                    eprintln!("no preset or samples for {}, it will be silent", preset_name);
+                    variants.push(Sound::from(Vec::new()));
+                }
+
+                EventSounds {
+                    event,
+                    variants,
+                    pitch_variation,
+                    gain,
+                }
+            })
+            .collect();
+
+        Self { events }
     }
 
-    fn load(data: &[u8]) -> Vec<i16> {
-        let cursor = std::io::Cursor::new(data);
-        let mut reader = lewton::inside_ogg::OggStreamReader::new(cursor).unwrap();
+    /// A voice playing one of the event's sounds, at a slightly random
+    /// pitch so that repeated events don't sound identical.
+    pub fn voice(&self, event: SoundEvent) -> Voice {
+        let sounds = self.sounds(event);
+        let variant = rand::thread_rng().gen_range(0, sounds.variants.len());
+        SoundEffects::vary(sounds, variant)
+    }
 
-        assert_eq!(reader.ident_hdr.audio_channels, CHANNEL_COUNT);
-        assert_eq!(
-            reader.ident_hdr.audio_sample_rate,
-            crate::audio::SAMPLE_RATE
-        );
+    /// The sound of `cells` cells being uncovered at once.
+    pub fn flood(&self, cells: usize) -> Voice {
+        let sounds = self.sounds(SoundEvent::Flood);
+        SoundEffects::vary(sounds, flood_step(cells).min(sounds.variants.len() - 1))
+    }
 
-        let mut buf = Vec::new();
-        while let Some(samples) = reader.read_dec_packet_itl().unwrap() {
-            buf.extend_from_slice(&samples);
-        }
+    fn sounds(&self, event: SoundEvent) -> &EventSounds {
+        self.events
+            .iter()
+            .find(|sounds| sounds.event == event)
+            .expect("every event is in the catalog")
+    }
+
+    fn vary(sounds: &EventSounds, variant: usize) -> Voice {
+        let mut rng = rand::thread_rng();
+        let sound = &sounds.variants[variant];
+        let pitch = if sounds.pitch_variation > 0.0 {
+            1.0 + rng.gen_range(-sounds.pitch_variation, sounds.pitch_variation)
+        } else {
+            1.0
+        };
+
+        Voice::new(sound).with_gain(sounds.gain).with_pitch(pitch)
+    }
 
-        buf
+    /// A sound that can't be decoded is reported and stays silent, rather
+    /// than keeping the game from running.
+    fn load(assets: &Assets, name: &str, sample_rate: u32) -> Sound {
+        assets
+            .try_load(name, |data| sound::decode(data, sample_rate))
+            .unwrap_or_else(|e| {
+                eprintln!("can't decode {}, it will be silent: {}", name, e);
+                Sound::from(Vec::new())
+            })
     }
 }
 
-lazy_static! {
-    pub static ref SOUND_EFFECTS: SoundEffects = SoundEffects::new();
+/// Which step a flood of `cells` cells is.
+fn flood_step(cells: usize) -> usize {
+    ((cells / 2).max(1) as f32).log2() as usize
+}
+
+/// A flood preset for the given step, lower and longer than the one before.
+fn flood_preset(preset: &Preset, step: usize) -> Preset {
+    let mut preset = preset.clone();
+    preset.frequency *= (-FLOOD_PITCH_STEP * step as f32).exp2();
+    preset.sustain *= 1.0 + FLOOD_LENGTH_STEP * step as f32;
+    preset.decay *= 1.0 + FLOOD_LENGTH_STEP * step as f32;
+    preset
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_floods_by_doublings() {
+        let steps: Vec<_> = [2, 3, 4, 7, 8, 16, 255, 256].iter().map(|&cells| flood_step(cells)).collect();
+        assert_eq!(steps, vec![0, 0, 1, 1, 2, 3, 6, 7]);
+    }
+
+    #[test]
+    fn decodes_the_embedded_samples() {
+        let assets = Assets::embedded();
+        for &(_, _, names, _, _) in CATALOG {
+            for name in names {
+                let sound = assets.try_load(name, |data| sound::decode(data, 44100));
+                assert!(!sound.unwrap().is_empty(), "{} is empty", name);
+            }
+        }
+    }
 }
@@ -0,0 +1,233 @@
+//! Decoding sounds into what the mixer plays: mono samples at the device's
+//! sample rate. Vorbis, WAV and FLAC files are understood, at any sample
+//! rate and with any number of channels.
+
+use std::io::Cursor;
+use std::sync::Arc;
+
+/// Decoded samples, shared with the audio callback while they play.
+pub type Sound = Arc<[i16]>;
+
+/// Samples from -1 to 1, with the channels interleaved.
+struct Decoded {
+    samples: Vec<f32>,
+    channels: usize,
+    sample_rate: u32,
+}
+
+/// Decodes a sound and converts it to mono at `sample_rate`.
+pub fn decode(data: &[u8], sample_rate: u32) -> Result<Sound, String> {
+    let decoded = if data.starts_with(b"OggS") {
+        decode_vorbis(data)
+    } else if data.starts_with(b"RIFF") {
+        decode_wav(data)
+    } else if data.starts_with(b"fLaC") {
+        decode_flac(data)
+    } else {
+        Err("not a Vorbis, WAV or FLAC file".into())
+    }?;
+
+    if decoded.channels == 0 || decoded.sample_rate == 0 {
+        return Err("no channels or no sample rate".into());
+    }
+
+    let mono = downmix(&decoded.samples, decoded.channels);
+    let resampled = resample(&mono, decoded.sample_rate, sample_rate);
+
+    Ok(resampled
+        .iter()
+        .map(|&sample| (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)
+        .collect())
+}
+
+fn decode_vorbis(data: &[u8]) -> Result<Decoded, String> {
+    let mut reader = lewton::inside_ogg::OggStreamReader::new(Cursor::new(data)).map_err(|e| e.to_string())?;
+
+    let mut samples = Vec::new();
+    while let Some(packet) = reader.read_dec_packet_itl().map_err(|e| e.to_string())? {
+        samples.extend(packet.iter().map(|&sample| sample as f32 / i16::MAX as f32));
+    }
+
+    Ok(Decoded {
+        samples,
+        channels: reader.ident_hdr.audio_channels as usize,
+        sample_rate: reader.ident_hdr.audio_sample_rate,
+    })
+}
+
+fn decode_wav(data: &[u8]) -> Result<Decoded, String> {
+    let mut reader = hound::WavReader::new(Cursor::new(data)).map_err(|e| e.to_string())?;
+    let spec = reader.spec();
+
+    let samples = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>(),
+        hound::SampleFormat::Int => {
+            let scale = full_scale(spec.bits_per_sample as u32);
+            reader
+                .samples::<i32>()
+                .map(|sample| sample.map(|sample| sample as f32 / scale))
+                .collect()
+        }
+    }
+    .map_err(|e| e.to_string())?;
+
+    Ok(Decoded {
+        samples,
+        channels: spec.channels as usize,
+        sample_rate: spec.sample_rate,
+    })
+}
+
+fn decode_flac(data: &[u8]) -> Result<Decoded, String> {
+    let mut reader = claxon::FlacReader::new(Cursor::new(data)).map_err(|e| e.to_string())?;
+    let info = reader.streaminfo();
+
+    let scale = full_scale(info.bits_per_sample);
+    let samples = reader
+        .samples()
+        .map(|sample| sample.map(|sample| sample as f32 / scale))
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+
+    Ok(Decoded {
+        samples,
+        channels: info.channels as usize,
+        sample_rate: info.sample_rate,
+    })
+}
+
+/// The largest value of a signed integer sample with `bits` bits.
+fn full_scale(bits: u32) -> f32 {
+    (1u64 << (bits.clamp(1, 32) - 1)) as f32
+}
+
+/// Averages the channels of each frame.
+fn downmix(samples: &[f32], channels: usize) -> Vec<f32> {
+    if channels == 1 {
+        return samples.to_vec();
+    }
+
+    samples
+        .chunks_exact(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect()
+}
+
+/// Converts mono samples from one sample rate to another, interpolating
+/// between them with Catmull-Rom splines.
+fn resample(samples: &[f32], from: u32, to: u32) -> Vec<f32> {
+    if from == to || samples.is_empty() {
+        return samples.to_vec();
+    }
+
+    let step = from as f64 / to as f64;
+    let length = (samples.len() as f64 / step).ceil() as usize;
+    let at = |i: i64| samples[i.clamp(0, samples.len() as i64 - 1) as usize];
+
+    (0..length)
+        .map(|i| {
+            let position = i as f64 * step;
+            let index = position.floor() as i64;
+            let t = (position - index as f64) as f32;
+
+            let (p0, p1, p2, p3) = (at(index - 1), at(index), at(index + 1), at(index + 2));
+            p1 + 0.5
+                * t
+                * (p2 - p0 + t * (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3 + t * (3.0 * (p1 - p2) + p3 - p0)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resamples_to_the_new_length() {
+        let samples = vec![0.25; 22050];
+        assert_eq!(resample(&samples, 22050, 44100).len(), 44100);
+        assert_eq!(resample(&samples, 44100, 22050).len(), 11025);
+        assert_eq!(resample(&samples, 48000, 44100).len(), 20259);
+        assert_eq!(resample(&samples, 44100, 44100).len(), 22050);
+        assert!(resample(&[], 22050, 44100).is_empty());
+
+        // a constant signal stays constant
+        assert!(resample(&samples, 48000, 44100).iter().all(|&s| (s - 0.25).abs() < 1e-6));
+    }
+
+    #[test]
+    fn interpolates_between_samples() {
+        let upsampled = resample(&[0.0, 1.0, 0.0], 1, 2);
+        assert_eq!(upsampled.len(), 6);
+        assert_eq!(upsampled[0], 0.0);
+        assert_eq!(upsampled[2], 1.0);
+        assert!(upsampled[1] > 0.0 && upsampled[1] < 1.0);
+    }
+
+    #[test]
+    fn averages_channels() {
+        assert_eq!(downmix(&[1.0, 0.0, 0.5, 0.5, -1.0, 1.0], 2), vec![0.5, 0.5, 0.0]);
+        assert_eq!(downmix(&[0.25, 0.5, 0.75], 3), vec![0.5]);
+        assert_eq!(downmix(&[0.1, 0.2], 1), vec![0.1, 0.2]);
+        // an incomplete last frame is dropped
+        assert_eq!(downmix(&[1.0, 1.0, 1.0], 2), vec![1.0]);
+    }
+
+    #[test]
+    fn scales_by_the_bit_depth() {
+        assert_eq!(full_scale(8), 128.0);
+        assert_eq!(full_scale(16), 32768.0);
+        assert_eq!(full_scale(24), 8388608.0);
+        assert_eq!(full_scale(32), 2147483648.0);
+    }
+
+    fn wav(bits: u16, samples: &[i32]) -> Vec<u8> {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 8000,
+            bits_per_sample: bits,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut data = Vec::new();
+        let mut writer = hound::WavWriter::new(Cursor::new(&mut data), spec).unwrap();
+        for &sample in samples {
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+        data
+    }
+
+    #[test]
+    fn decodes_wavs_of_any_bit_depth_to_the_same_levels() {
+        for &bits in &[8u16, 16, 24, 32] {
+            let max = ((1i64 << (bits - 1)) - 1) as i32;
+            let decoded = decode_wav(&wav(bits, &[0, max / 2, -max - 1])).unwrap();
+
+            assert_eq!(decoded.samples.len(), 3, "{} bits", bits);
+            assert_eq!(decoded.samples[0], 0.0);
+            assert!((decoded.samples[1] - 0.5).abs() < 0.01, "{} bits: {:?}", bits, decoded.samples);
+            assert_eq!(decoded.samples[2], -1.0, "{} bits", bits);
+        }
+    }
+
+    #[test]
+    fn decodes_to_mono_at_the_device_rate() {
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 22050,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut data = Vec::new();
+        let mut writer = hound::WavWriter::new(Cursor::new(&mut data), spec).unwrap();
+        for _ in 0..1000 {
+            writer.write_sample(i16::MAX).unwrap();
+            writer.write_sample(0i16).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let sound = decode(&data, 44100).unwrap();
+        assert_eq!(sound.len(), 2000);
+        assert!(sound.iter().all(|&sample| (sample - i16::MAX / 2).abs() <= 1));
+    }
+}
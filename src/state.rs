@@ -1,10 +1,6 @@
-use crate::field::{Field, RevealResult};
+use crate::field::{Field, RevealResult, ToggleFlagResult};
 use std::time::{Duration, Instant};
 
-const FIELD_WIDTH: u8 = 8;
-const FIELD_HEIGHT: u8 = 8;
-const MINE_COUNT: u16 = 10;
-
 #[derive(Debug)]
 enum Timer {
     NotStarted,
@@ -16,13 +12,16 @@ enum Timer {
 pub struct State {
     field: Field,
     timer: Timer,
+    /// Reveals, chords and flags, whether or not they did anything.
+    clicks: u32,
 }
 
 impl State {
-    pub fn new() -> Self {
+    pub fn new(field: Field) -> Self {
         Self {
-            field: Field::new(FIELD_WIDTH, FIELD_HEIGHT, MINE_COUNT),
+            field,
             timer: Timer::NotStarted,
+            clicks: 0,
         }
     }
 
@@ -53,25 +52,63 @@ impl State {
         }
     }
 
-    pub fn reveal(&mut self, x: u8, y: u8) -> RevealResult {
+    pub fn reveal(&mut self, x: u16, y: u16) -> RevealResult {
         if let Timer::NotStarted = self.timer {
             self.start_timer();
         }
 
+        self.clicks += 1;
         let result = self.field.reveal(x, y);
+        self.stop_timer_if_over(&result);
+
+        result
+    }
+
+    pub fn chord(&mut self, x: u16, y: u16) -> RevealResult {
+        self.clicks += 1;
+        let result = self.field.chord(x, y);
+        self.stop_timer_if_over(&result);
+
+        result
+    }
+
+    fn stop_timer_if_over(&mut self, result: &RevealResult) {
         match result {
             RevealResult::Mine => self.stop_timer(),
+            RevealResult::Success(_) if self.field.is_cleared() => self.stop_timer(),
             _ => (),
         }
+    }
 
-        result
+    /// Whether every safe cell has been revealed.
+    pub fn is_won(&self) -> bool {
+        self.field.is_cleared()
+    }
+
+    pub fn toggle_flag(&mut self, x: u16, y: u16) -> ToggleFlagResult {
+        self.clicks += 1;
+        self.field.toggle_flag(x, y)
+    }
+
+    pub fn clicks(&self) -> u32 {
+        self.clicks
     }
 
-    pub fn toggle_flag(&mut self, x: u8, y: u8) {
-        self.field.toggle_flag(x, y);
+    /// Flags a mine that was left unflagged, once the game is won.
+    pub fn flag_mine(&mut self, x: u16, y: u16) {
+        if self.is_won() {
+            self.field.flag_mine(x, y);
+        }
+    }
+
+    /// Shows where a mine was, once the game is lost.
+    pub fn expose_mine(&mut self, x: u16, y: u16) {
+        if self.game_over() && !self.is_won() {
+            self.field.expose_mine(x, y);
+        }
     }
 
-    pub fn mines_remaining(&self) -> i32 {
-        self.field.mine_count() as i32 - self.field.flagged_cells() as i32
+    pub fn mines_remaining(&self) -> i64 {
+        self.field.mine_count() as i64 - self.field.placed_flags() as i64
     }
 }
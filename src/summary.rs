@@ -0,0 +1,69 @@
+//! The overlay shown after a win, with the time and how the game went.
+
+use crate::font;
+use crate::overlay::{self, COLUMN_GAP, HIGHLIGHT_COLOR, TEXT_COLOR};
+use sdl2::rect::Rect;
+use sdl2::render::WindowCanvas;
+use std::time::Duration;
+
+const TITLE: &str = "CLEARED";
+const HINT: &str = "CLICK TO CLOSE";
+
+#[derive(Debug, Clone)]
+pub struct Summary {
+    pub time: Duration,
+    /// The best time before this one counted, if there was one.
+    pub previous_best: Option<Duration>,
+    pub size: (u16, u16),
+    pub mines: u32,
+    pub clicks: u32,
+}
+
+impl Summary {
+    fn is_new_best(&self) -> bool {
+        self.previous_best.map(|best| self.time < best).unwrap_or(true)
+    }
+
+    pub fn render(&self, canvas: &mut WindowCanvas, area: Rect, display_scale: f32) {
+        let scale = overlay::text_scale(display_scale);
+
+        let best = match self.previous_best {
+            Some(best) if !self.is_new_best() => format_time(best),
+            _ => "NEW".to_string(),
+        };
+        let rows = [
+            ("TIME", format_time(self.time)),
+            ("BEST", best),
+            ("BOARD", format!("{}X{}", self.size.0, self.size.1)),
+            ("MINES", self.mines.to_string()),
+            ("CLICKS", self.clicks.to_string()),
+        ];
+
+        let label_width = rows.iter().map(|(label, _)| font::text_width(label)).max().unwrap();
+        let value_width = rows.iter().map(|(_, value)| font::text_width(value)).max().unwrap();
+        let width = (label_width + COLUMN_GAP + value_width).max(font::text_width(HINT));
+        let lines = rows.len() as u32 + 4;
+        let panel = overlay::draw_panel(canvas, area, width, overlay::lines_height(lines), scale);
+        let at = |x: u32, line: u32| overlay::position(panel, x, line, scale);
+
+        canvas.set_draw_color(HIGHLIGHT_COLOR);
+        font::draw(canvas, TITLE, at((width - font::text_width(TITLE)) / 2, 0), scale);
+
+        for (i, (label, value)) in rows.iter().enumerate() {
+            let line = i as u32 + 2;
+            let highlighted = *label == "BEST" && self.is_new_best();
+            canvas.set_draw_color(if highlighted { HIGHLIGHT_COLOR } else { TEXT_COLOR });
+            font::draw(canvas, label, at(0, line), scale);
+            font::draw(canvas, value, at(width - font::text_width(value), line), scale);
+        }
+
+        canvas.set_draw_color(TEXT_COLOR);
+        font::draw(canvas, HINT, at((width - font::text_width(HINT)) / 2, lines - 1), scale);
+    }
+}
+
+/// Like `1:05.3`.
+fn format_time(time: Duration) -> String {
+    let tenths = time.as_millis() / 100;
+    format!("{}:{:02}.{}", tenths / 600, tenths / 10 % 60, tenths % 10)
+}
@@ -0,0 +1,282 @@
+//! Sound effects generated from a handful of parameters, in the spirit of
+//! sfxr: an oscillator or noise, sliding in pitch, shaped by an envelope
+//! and filtered.
+//!
+//! Presets are kept in a `config` file, one parameter per line, keyed by
+//! the preset's name and the parameter:
+//!
+//! ```text
+//! hover.wave = triangle
+//! hover.frequency = 1800
+//! hover.decay = 0.03
+//! ```
+
+use crate::config;
+use crate::sound::Sound;
+use std::f32::consts::TAU;
+
+/// Longest sound a preset can make, in seconds.
+const MAX_LENGTH: f32 = 10.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wave {
+    Square,
+    Sawtooth,
+    Triangle,
+    Sine,
+    /// A new random level every period, so the frequency sets how bright
+    /// the noise is.
+    Noise,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Preset {
+    pub wave: Wave,
+    /// Starting pitch, in Hz.
+    pub frequency: f32,
+    /// How fast the pitch changes, in octaves per second.
+    pub slide: f32,
+    /// Fraction of each period a square wave is high.
+    pub duty: f32,
+    /// How far the pitch wobbles, in semitones, and how often, in Hz.
+    pub vibrato_depth: f32,
+    pub vibrato_rate: f32,
+    /// Envelope stages, in seconds: fading in, holding, and fading out.
+    pub attack: f32,
+    pub sustain: f32,
+    pub decay: f32,
+    /// Cutoffs in Hz; frequencies above `lowpass` and below `highpass` are
+    /// softened. 0 leaves the sound unfiltered.
+    pub lowpass: f32,
+    pub highpass: f32,
+    pub volume: f32,
+}
+
+impl Default for Preset {
+    fn default() -> Self {
+        Self {
+            wave: Wave::Square,
+            frequency: 440.0,
+            slide: 0.0,
+            duty: 0.5,
+            vibrato_depth: 0.0,
+            vibrato_rate: 0.0,
+            attack: 0.0,
+            sustain: 0.1,
+            decay: 0.1,
+            lowpass: 0.0,
+            highpass: 0.0,
+            volume: 0.5,
+        }
+    }
+}
+
+impl Preset {
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        if key == "wave" {
+            self.wave = match value {
+                "square" => Wave::Square,
+                "sawtooth" => Wave::Sawtooth,
+                "triangle" => Wave::Triangle,
+                "sine" => Wave::Sine,
+                "noise" => Wave::Noise,
+                _ => return Err(format!("unknown wave {}", value)),
+            };
+            return Ok(());
+        }
+
+        let number = value
+            .parse::<f32>()
+            .ok()
+            .filter(|number| number.is_finite())
+            .ok_or_else(|| format!("invalid number {}", value))?;
+        let non_negative = || {
+            Some(number)
+                .filter(|&number| number >= 0.0)
+                .ok_or_else(|| format!("{} can't be negative", key))
+        };
+
+        match key {
+            "frequency" => self.frequency = non_negative()?,
+            "slide" => self.slide = number,
+            "duty" => self.duty = number.clamp(0.0, 1.0),
+            "vibrato_depth" => self.vibrato_depth = number,
+            "vibrato_rate" => self.vibrato_rate = non_negative()?,
+            "attack" => self.attack = non_negative()?,
+            "sustain" => self.sustain = non_negative()?,
+            "decay" => self.decay = non_negative()?,
+            "lowpass" => self.lowpass = non_negative()?,
+            "highpass" => self.highpass = non_negative()?,
+            "volume" => self.volume = number.clamp(0.0, 1.0),
+            _ => return Err(format!("unknown parameter {}", key)),
+        }
+
+        Ok(())
+    }
+
+    /// Length of the sound, in seconds.
+    pub fn length(&self) -> f32 {
+        (self.attack + self.sustain + self.decay).min(MAX_LENGTH)
+    }
+
+    /// Generates the sound at `sample_rate`.
+    pub fn render(&self, sample_rate: u32) -> Sound {
+        let rate = sample_rate as f32;
+        let length = (self.length() * rate) as usize;
+        let nyquist = rate / 2.0;
+
+        let mut noise = Noise::default();
+        let mut noise_level = noise.next();
+        let mut phase = 0.0f32;
+        let mut lowpassed = 0.0;
+        let mut highpass_state = 0.0;
+        let lowpass = smoothing(self.lowpass, rate);
+        let highpass = smoothing(self.highpass, rate);
+
+        (0..length)
+            .map(|i| {
+                let t = i as f32 / rate;
+
+                let vibrato = self.vibrato_depth / 12.0 * (TAU * self.vibrato_rate * t).sin();
+                let frequency = (self.frequency * (self.slide * t + vibrato).exp2()).clamp(1.0, nyquist);
+                phase += frequency / rate;
+                if phase >= 1.0 {
+                    phase = phase.fract();
+                    noise_level = noise.next();
+                }
+
+                let mut sample = match self.wave {
+                    Wave::Square => {
+                        if phase < self.duty {
+                            1.0
+                        } else {
+                            -1.0
+                        }
+                    }
+                    Wave::Sawtooth => 2.0 * phase - 1.0,
+                    Wave::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+                    Wave::Sine => (TAU * phase).sin(),
+                    Wave::Noise => noise_level,
+                };
+
+                if let Some(lowpass) = lowpass {
+                    lowpassed += (sample - lowpassed) * lowpass;
+                    sample = lowpassed;
+                }
+                if let Some(highpass) = highpass {
+                    highpass_state += (sample - highpass_state) * highpass;
+                    sample -= highpass_state;
+                }
+
+                let level = sample * self.envelope(t) * self.volume;
+                (level.clamp(-1.0, 1.0) * i16::MAX as f32) as i16
+            })
+            .collect()
+    }
+
+    /// How loud the sound is `t` seconds in, from 0 to 1.
+    fn envelope(&self, t: f32) -> f32 {
+        if t < self.attack {
+            t / self.attack
+        } else if t < self.attack + self.sustain {
+            1.0
+        } else if self.decay > 0.0 {
+            (1.0 - (t - self.attack - self.sustain) / self.decay).max(0.0)
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Coefficient of a one pole filter with its cutoff at `cutoff`, if it has
+/// one.
+fn smoothing(cutoff: f32, sample_rate: f32) -> Option<f32> {
+    if cutoff <= 0.0 {
+        return None;
+    }
+
+    Some(1.0 - (-TAU * cutoff / sample_rate).exp())
+}
+
+/// A xorshift generator, seeded the same every time so a preset always
+/// sounds the same.
+struct Noise(u32);
+
+impl Default for Noise {
+    fn default() -> Self {
+        Noise(0x9e37_79b9)
+    }
+}
+
+impl Noise {
+    /// A level from -1 to 1.
+    fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0 as f32 / u32::MAX as f32 * 2.0 - 1.0
+    }
+}
+
+/// Named presets, in the order they were first mentioned.
+#[derive(Debug, Clone, Default)]
+pub struct Presets {
+    presets: Vec<(String, Preset)>,
+}
+
+impl Presets {
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        Ok(Self {
+            presets: config::parse_named(data, Preset::set)?,
+        })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Preset> {
+        self.presets
+            .iter()
+            .find(|(preset_name, _)| preset_name == name)
+            .map(|(_, preset)| preset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_embedded_presets() {
+        let presets = Presets::parse(include_bytes!("sfx/presets.txt")).unwrap();
+
+        for name in &["hover", "timer", "wasted", "flood"] {
+            assert!(presets.get(name).is_some(), "{} is missing", name);
+        }
+    }
+
+    #[test]
+    fn renders_as_long_as_the_envelope() {
+        let preset = Preset {
+            attack: 0.25,
+            sustain: 0.5,
+            decay: 0.25,
+            ..Preset::default()
+        };
+        assert_eq!(preset.render(8000).len(), 8000);
+
+        for wave in &[Wave::Square, Wave::Sawtooth, Wave::Triangle, Wave::Sine, Wave::Noise] {
+            let preset = Preset {
+                wave: *wave,
+                ..preset.clone()
+            };
+            assert_eq!(preset.render(44100).len(), 44100);
+        }
+    }
+
+    #[test]
+    fn caps_the_length() {
+        let preset = Preset {
+            sustain: 1000.0,
+            ..Preset::default()
+        };
+        assert_eq!(preset.render(100).len(), (MAX_LENGTH * 100.0) as usize);
+    }
+}
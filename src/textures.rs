@@ -1,68 +1,460 @@
+use crate::assets::Assets;
+use crate::field::Cell;
+use crate::font;
+use crate::geometry::Grid;
+use crate::theme::{Color, Theme};
+use crate::tile_art::{self, Tile, TileArt};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
 use sdl2::render::{Texture, TextureCreator, WindowCanvas};
 use sdl2::video::WindowContext;
 
-const BIT_DEPTH: png::BitDepth = png::BitDepth::Eight;
-const COLOR_TYPE: png::ColorType = png::ColorType::RGBA;
+/// Largest image a theme may use, in pixels.
+const MAX_IMAGE_SIZE: u32 = 4096;
 
-pub struct Textures {
-    _texture_creator: TextureCreator<WindowContext>,
-
-    pub mine: Texture,
-    pub unrevealed: Texture,
-    pub hover: Texture,
-    pub flag: Texture,
-    pub hover_flag: Texture,
-    pub numbers: [Texture; 9],
+/// Size of the tile art, in pixels.
+pub const TILE_SIZE: u32 = 128;
+
+/// Color of the digits generated for counts the tile art doesn't cover.
+const GENERATED_NUMBER_COLOR: [u8; 3] = [0xc7, 0x58, 0x58];
+/// Area of a tile generated digits are fitted into, in texture pixels.
+const GENERATED_NUMBER_AREA: (u32, u32, u32, u32) = (16, 34, 96, 60);
+
+/// How far a pixel of a number tile has to be from the blank tile's to
+/// count as part of the number, summed over the channels.
+const INK_THRESHOLD: i32 = 96;
+/// How bright the debris of cells without a number is, next to the blank
+/// tile.
+const BLANK_DEBRIS_SHADE: f32 = 0.6;
+
+const COUNT_OVERLAY_COLOR: [u8; 3] = [0xff, 0xff, 0xff];
+const COUNT_OVERLAY_SHADOW: [u8; 3] = [0x16, 0x23, 0x3c];
+/// Corner of a tile the mine and flag counts are fitted into.
+const COUNT_OVERLAY_AREA: (u32, u32, u32, u32) = (72, 76, 48, 44);
+
+/// Alpha of the shade drawn over the wrapped context around the field.
+const CONTEXT_SHADE: u8 = 128;
+
+/// Rasterized tile sets kept around for recently used cell sizes.
+const MAX_CACHED_SIZES: usize = 4;
+/// Cells bigger than this use the tiles rasterized at this size.
+const MAX_RASTER_SIZE: u32 = 1024;
+/// How long the cell size has to stay the same before the tiles are
+/// rasterized for it, so zooming and resizing don't stall on sizes that
+/// are only passed through.
+const SETTLE_TIME: Duration = Duration::from_millis(200);
+
+/// One texture per kind of tile.
+struct TileSet {
+    mine: Texture,
+    unrevealed: Texture,
+    hover: Texture,
+    flag: Texture,
+    hover_flag: Texture,
+    /// Indexed by the number of neighboring mines, up to 8.
+    numbers: Vec<Texture>,
 }
 
-macro_rules! load {
-    ($texture_creator:expr, $path:expr) => {
-        Textures::load(&$texture_creator, include_bytes!($path))
-    };
+impl TileSet {
+    fn rasterize(texture_creator: &TextureCreator<WindowContext>, art: &TileArt, size: u32, grid: Grid) -> Self {
+        let tile = |tile| Textures::create_cell(texture_creator, art.rasterize(tile, size), grid);
+
+        Self {
+            mine: tile(Tile::Mine),
+            unrevealed: tile(Tile::Unrevealed),
+            hover: tile(Tile::Hover),
+            flag: tile(Tile::Flag),
+            hover_flag: tile(Tile::HoverFlag),
+            numbers: (0..=tile_art::MAX_NUMBER).map(|n| tile(Tile::Number(n))).collect(),
+        }
+    }
+
+    fn get(&self, tile: Tile) -> &Texture {
+        match tile {
+            Tile::Unrevealed => &self.unrevealed,
+            Tile::Hover => &self.hover,
+            Tile::Mine => &self.mine,
+            Tile::Flag => &self.flag,
+            Tile::HoverFlag => &self.hover_flag,
+            Tile::Number(n) => &self.numbers[n as usize],
+        }
+    }
+
+    /// Frees the textures; they don't free themselves.
+    fn destroy(self) {
+        let textures = vec![self.mine, self.unrevealed, self.hover, self.flag, self.hover_flag];
+        for texture in textures.into_iter().chain(self.numbers) {
+            unsafe { texture.destroy() };
+        }
+    }
+}
+
+pub struct Textures {
+    texture_creator: TextureCreator<WindowContext>,
+    /// Tiles are cut to the shape of its cells.
+    grid: Grid,
+    /// What generated numbers are drawn over.
+    blank: Image,
+    /// The theme's 0 tile, drawn over instead if it has one.
+    themed_blank: Option<Image>,
+
+    /// The fixed size tiles, used when the vector art isn't available.
+    fixed: TileSet,
+    art: Option<TileArt>,
+    /// Most recently used last.
+    rasterized: Vec<(u32, TileSet)>,
+    cell_size: u32,
+    /// When the cell size last changed to one that isn't rasterized yet.
+    resized_at: Option<Instant>,
+    /// Tiles replaced by the current theme, drawn at whatever size they are.
+    themed: HashMap<Tile, Texture>,
+
+    /// Numbers above what the tile art covers, drawn over the blank tile.
+    generated_numbers: HashMap<u16, Texture>,
+    /// Transparent tiles showing how many mines or flags a cell holds.
+    count_overlays: HashMap<u8, Texture>,
+    /// Darkens a cell drawn under it.
+    shade: Texture,
+
+    /// What revealed cells break into, indexed by their number, sampled
+    /// from the tiles drawn: the vector art's, or else the fixed size ones.
+    debris_colors: Vec<Color>,
+    /// Those of the numbers the current theme replaces.
+    themed_debris_colors: HashMap<u8, Color>,
 }
 
 impl Textures {
-    pub fn new(canvas: &WindowCanvas) -> Self {
-        let tc = canvas.texture_creator();
+    pub fn new(canvas: &WindowCanvas, assets: &Assets, grid: Grid) -> Self {
+        let texture_creator = canvas.texture_creator();
+        let (fixed, fixed_debris_colors) = Textures::load_tile_set(&texture_creator, assets, grid);
+        let art = Textures::load_art(assets);
+        let debris_colors = art
+            .as_ref()
+            .map(Textures::art_debris_colors)
+            .unwrap_or(fixed_debris_colors);
+
+        let mut shade = Image::transparent(TILE_SIZE, TILE_SIZE);
+        for pixel in shade.pixels.chunks_mut(4) {
+            pixel[3] = CONTEXT_SHADE;
+        }
+        let mut shade = Textures::create_cell(&texture_creator, shade, grid);
+        shade.set_blend_mode(sdl2::render::BlendMode::Blend);
 
         Self {
-            mine: load!(tc, "textures/mine.png"),
-            unrevealed: load!(tc, "textures/unrevealed.png"),
-            hover: load!(tc, "textures/hover.png"),
-            flag: load!(tc, "textures/flag.png"),
-            hover_flag: load!(tc, "textures/hover_flag.png"),
-            numbers: [
-                load!(tc, "textures/0.png"),
-                load!(tc, "textures/1.png"),
-                load!(tc, "textures/2.png"),
-                load!(tc, "textures/3.png"),
-                load!(tc, "textures/4.png"),
-                load!(tc, "textures/5.png"),
-                load!(tc, "textures/6.png"),
-                load!(tc, "textures/7.png"),
-                load!(tc, "textures/8.png"),
-            ],
-
-            _texture_creator: tc,
-        }
-    }
-
-    fn load(texture_creator: &TextureCreator<WindowContext>, data: &[u8]) -> Texture {
-        let decoder = png::Decoder::new(std::io::Cursor::new(data));
-        let (info, mut reader) = decoder.read_info().unwrap();
+            blank: assets.load("src/textures/0.png", Image::decode),
+            themed_blank: None,
 
-        let mut buf = vec![0; info.buffer_size()];
-        reader.next_frame(&mut buf).unwrap();
+            fixed,
+            art,
+            rasterized: Vec::new(),
+            cell_size: TILE_SIZE,
+            resized_at: None,
+            themed: HashMap::new(),
+
+            generated_numbers: HashMap::new(),
+            count_overlays: HashMap::new(),
+            shade,
+
+            debris_colors,
+            themed_debris_colors: HashMap::new(),
+
+            texture_creator,
+            grid,
+        }
+    }
+
+    /// Loads the tile art again, after it changed on disk.
+    pub fn reload(&mut self, assets: &Assets) {
+        let (fixed, fixed_debris_colors) = Textures::load_tile_set(&self.texture_creator, assets, self.grid);
+        std::mem::replace(&mut self.fixed, fixed).destroy();
+        for (_, tiles) in self.rasterized.drain(..) {
+            tiles.destroy();
+        }
+        self.forget_generated();
+
+        self.blank = assets.load("src/textures/0.png", Image::decode);
+        self.art = Textures::load_art(assets);
+        self.debris_colors = self
+            .art
+            .as_ref()
+            .map(Textures::art_debris_colors)
+            .unwrap_or(fixed_debris_colors);
+        self.set_cell_size(self.cell_size);
+    }
+
+    /// Loads the fixed size tiles, and the colors of the debris of each
+    /// number, sampled from them.
+    fn load_tile_set(
+        texture_creator: &TextureCreator<WindowContext>,
+        assets: &Assets,
+        grid: Grid,
+    ) -> (TileSet, Vec<Color>) {
+        let image = |name: &str| assets.load(&format!("src/textures/{}.png", name), Image::decode);
+        let create = |image| Textures::create_cell(texture_creator, image, grid);
+        let load = |name: &str| create(image(name));
+
+        let numbers: Vec<Image> = (0..=tile_art::MAX_NUMBER).map(|n| image(&n.to_string())).collect();
+        let debris_colors = Textures::sample_debris_colors(&numbers);
+
+        let tiles = TileSet {
+            mine: load("mine"),
+            unrevealed: load("unrevealed"),
+            hover: load("hover"),
+            flag: load("flag"),
+            hover_flag: load("hover_flag"),
+            numbers: numbers.into_iter().map(create).collect(),
+        };
+
+        (tiles, debris_colors)
+    }
+
+    /// The debris colors of the vector art's numbers.
+    fn art_debris_colors(art: &TileArt) -> Vec<Color> {
+        let numbers: Vec<Image> = (0..=tile_art::MAX_NUMBER)
+            .map(|n| art.rasterize(Tile::Number(n), TILE_SIZE))
+            .collect();
+        Textures::sample_debris_colors(&numbers)
+    }
+
+    /// The debris color of each of `numbers`, from 0 up.
+    fn sample_debris_colors(numbers: &[Image]) -> Vec<Color> {
+        numbers
+            .iter()
+            .enumerate()
+            .map(|(n, number)| Textures::sample_debris_color(n as u8, number, &numbers[0]))
+            .map(|color| color.unwrap_or(generated_number_color()))
+            .collect()
+    }
 
-        // ensure the format is standard
-        assert_eq!(info.bit_depth, BIT_DEPTH);
-        assert_eq!(info.color_type, COLOR_TYPE);
+    /// The color of the number drawn on `image` over `blank`, or a shade
+    /// of the tile for 0.
+    fn sample_debris_color(n: u8, image: &Image, blank: &Image) -> Option<Color> {
+        if n == 0 {
+            let (r, g, b) = image.average_color(|_| true)?;
+            let shade = |v: u8| (v as f32 * BLANK_DEBRIS_SHADE) as u8;
+            return Some((shade(r), shade(g), shade(b)));
+        }
+
+        image.ink_color(blank)
+    }
 
+    fn load_art(assets: &Assets) -> Option<TileArt> {
+        match TileArt::load(&assets.read("tiles.svg")) {
+            Ok(art) => Some(art),
+            Err(e) => {
+                eprintln!("can't use tiles.svg, falling back to fixed size tiles: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Makes the tiles match cells of the given size in pixels. Sizes that
+    /// haven't been rasterized recently are drawn with the nearest one that
+    /// has until `update` gets to them, except for the first.
+    pub fn set_cell_size(&mut self, cell_size: u32) {
+        let cell_size = cell_size.clamp(1, MAX_RASTER_SIZE);
+        self.cell_size = cell_size;
+
+        if self.art.is_none() {
+            return;
+        }
+        match self.rasterized.iter().position(|(size, _)| *size == cell_size) {
+            Some(i) => {
+                let entry = self.rasterized.remove(i);
+                self.rasterized.push(entry);
+                self.resized_at = None;
+            }
+            None if self.rasterized.is_empty() => self.rasterize(),
+            None => self.resized_at = Some(Instant::now()),
+        }
+    }
+
+    /// Rasterizes the tiles for the cell size once it has settled.
+    pub fn update(&mut self) {
+        match self.resized_at {
+            Some(resized_at) if resized_at.elapsed() >= SETTLE_TIME => self.rasterize(),
+            _ => (),
+        }
+    }
+
+    fn rasterize(&mut self) {
+        self.resized_at = None;
+        let art = match &self.art {
+            Some(art) => art,
+            None => return,
+        };
+
+        let tiles = TileSet::rasterize(&self.texture_creator, art, self.cell_size, self.grid);
+        self.rasterized.push((self.cell_size, tiles));
+
+        if self.rasterized.len() > MAX_CACHED_SIZES {
+            let (_, oldest) = self.rasterized.remove(0);
+            oldest.destroy();
+        }
+    }
+
+    /// The tiles rasterized nearest to the cell size, scaled to it when
+    /// drawn, or the fixed size ones without the vector art.
+    fn tiles(&self) -> &TileSet {
+        let cell_size = self.cell_size as i64;
+        self.rasterized
+            .iter()
+            .min_by_key(|(size, _)| (*size as i64 - cell_size).abs())
+            .map(|(_, tiles)| tiles)
+            .unwrap_or(&self.fixed)
+    }
+
+    /// Swaps in the tiles `theme` replaces.
+    pub fn set_theme(&mut self, theme: &Theme) {
+        for (_, texture) in self.themed.drain() {
+            unsafe { texture.destroy() };
+        }
+        self.forget_generated();
+
+        self.themed_blank = None;
+        for (tile, image) in &theme.tiles {
+            if *tile == Tile::Number(0) {
+                self.themed_blank = Some(image.clone());
+            }
+
+            let texture = Textures::create_cell(&self.texture_creator, image.clone(), self.grid);
+            self.themed.insert(*tile, texture);
+        }
+
+        // numbers the theme's art can't be sampled from keep the built in
+        // colors
+        let blank = self.blank();
+        let debris_colors = theme
+            .tiles
+            .iter()
+            .filter_map(|(tile, image)| match *tile {
+                Tile::Number(n) => Some((n, Textures::sample_debris_color(n, image, blank)?)),
+                _ => None,
+            })
+            .collect();
+        self.themed_debris_colors = debris_colors;
+    }
+
+    /// Frees the textures generated over the blank tile, after it changed.
+    fn forget_generated(&mut self) {
+        let numbers = self.generated_numbers.drain().map(|(_, texture)| texture);
+        for texture in numbers.chain(self.count_overlays.drain().map(|(_, texture)| texture)) {
+            unsafe { texture.destroy() };
+        }
+    }
+
+    /// Picks the texture a cell should be drawn with, generating it if
+    /// it isn't part of the tile art.
+    pub fn for_cell(&mut self, cell: &Cell, hovering: bool) -> &Texture {
+        let n = cell.neighboring_mines;
+        if cell.revealed && !cell.has_mine() && n > tile_art::MAX_NUMBER as u16 {
+            return self.generated_number(n);
+        }
+
+        let tile = if cell.revealed {
+            if cell.has_mine() {
+                Tile::Mine
+            } else {
+                Tile::Number(n as u8)
+            }
+        } else if hovering {
+            if cell.is_flagged() {
+                Tile::HoverFlag
+            } else {
+                Tile::Hover
+            }
+        } else if cell.is_flagged() {
+            Tile::Flag
+        } else {
+            Tile::Unrevealed
+        };
+        self.tile(tile)
+    }
+
+    /// Numbers above what the tile art covers, drawn over the blank tile.
+    fn generated_number(&mut self, n: u16) -> &Texture {
+        if !self.generated_numbers.contains_key(&n) {
+            let mut image = self.blank().clone();
+            let area = image.scale_area(GENERATED_NUMBER_AREA);
+            image.draw_text(&n.to_string(), area, GENERATED_NUMBER_COLOR, None);
+            let texture = Textures::create_cell(&self.texture_creator, image, self.grid);
+            self.generated_numbers.insert(n, texture);
+        }
+
+        &self.generated_numbers[&n]
+    }
+
+    fn tile(&self, tile: Tile) -> &Texture {
+        self.themed.get(&tile).unwrap_or_else(|| self.tiles().get(tile))
+    }
+
+    /// The count to draw over the cell when it holds more than one flag or
+    /// revealed mine.
+    pub fn overlay_for_cell(&mut self, cell: &Cell) -> Option<&Texture> {
+        let count = Textures::overlay_count(cell)?;
+        if !self.count_overlays.contains_key(&count) {
+            let blank = self.blank();
+            let mut image = Image::transparent(blank.width, blank.height);
+            let area = image.scale_area(COUNT_OVERLAY_AREA);
+            image.draw_text(
+                &count.to_string(),
+                area,
+                COUNT_OVERLAY_COLOR,
+                Some(COUNT_OVERLAY_SHADOW),
+            );
+            let texture = Textures::create(&self.texture_creator, image);
+            self.count_overlays.insert(count, texture);
+        }
+
+        Some(&self.count_overlays[&count])
+    }
+
+    /// Drawn over cells across wrapping edges.
+    pub fn shade(&self) -> &Texture {
+        &self.shade
+    }
+
+    fn overlay_count(cell: &Cell) -> Option<u8> {
+        let count = if cell.revealed { cell.mines } else { cell.flags };
+        if count > 1 {
+            Some(count)
+        } else {
+            None
+        }
+    }
+
+    /// The color a revealed cell with `n` neighboring mines breaks into.
+    pub fn debris_color(&self, n: u16) -> Color {
+        if n > tile_art::MAX_NUMBER as u16 {
+            return generated_number_color();
+        }
+
+        let n = n as u8;
+        self.themed_debris_colors
+            .get(&n)
+            .copied()
+            .unwrap_or(self.debris_colors[n as usize])
+    }
+
+    fn blank(&self) -> &Image {
+        self.themed_blank.as_ref().unwrap_or(&self.blank)
+    }
+
+    /// Like `create`, first cutting `image` to the shape of `grid`'s cells.
+    fn create_cell(texture_creator: &TextureCreator<WindowContext>, mut image: Image, grid: Grid) -> Texture {
+        if grid == Grid::Hex {
+            image.cut_hexagon();
+        }
+
+        Textures::create(texture_creator, image)
+    }
+
+    fn create(texture_creator: &TextureCreator<WindowContext>, mut image: Image) -> Texture {
         let surface = sdl2::surface::Surface::from_data(
-            &mut buf,
-            info.width,
-            info.height,
-            4 * info.width,
+            &mut image.pixels,
+            image.width,
+            image.height,
+            4 * image.width,
             sdl2::pixels::PixelFormatEnum::RGBA32,
         )
         .unwrap();
@@ -70,11 +462,247 @@ impl Textures {
             .create_texture_from_surface(surface)
             .unwrap();
 
-        // ensure the texture is filtered
+        // ensure the texture is filtered, picking from a mipmap so that
+        // tiles shrunk far below the art's size still look smooth
         texture.gl_with_bind(|_, _| unsafe {
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+            gl::GenerateMipmap(gl::TEXTURE_2D);
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_MIN_FILTER,
+                gl::LINEAR_MIPMAP_LINEAR as i32,
+            );
         });
 
         texture
     }
 }
+
+fn generated_number_color() -> Color {
+    let [r, g, b] = GENERATED_NUMBER_COLOR;
+    (r, g, b)
+}
+
+/// Decoded RGBA pixels.
+#[derive(Debug, Clone)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    /// Decodes a PNG, converting it to 8 bit RGBA.
+    pub fn decode(data: &[u8]) -> Result<Self, String> {
+        let mut decoder = png::Decoder::new(std::io::Cursor::new(data));
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+        let (info, mut reader) = decoder.read_info().map_err(|e| e.to_string())?;
+
+        // checked before the pixels are allocated, which the header alone
+        // could make gigabytes
+        if info.width == 0 || info.height == 0 {
+            return Err("image is empty".into());
+        }
+        if info.width > MAX_IMAGE_SIZE || info.height > MAX_IMAGE_SIZE {
+            return Err(format!("image is larger than {0}x{0}", MAX_IMAGE_SIZE));
+        }
+        if info.bit_depth != png::BitDepth::Eight {
+            return Err(format!("unsupported bit depth {:?}", info.bit_depth));
+        }
+
+        let mut buf = vec![0; info.buffer_size()];
+        reader.next_frame(&mut buf).map_err(|e| e.to_string())?;
+
+        let pixels = match info.color_type {
+            png::ColorType::RGBA => buf,
+            png::ColorType::RGB => buf.chunks(3).flat_map(|p| vec![p[0], p[1], p[2], 0xff]).collect(),
+            png::ColorType::GrayscaleAlpha => buf.chunks(2).flat_map(|p| vec![p[0], p[0], p[0], p[1]]).collect(),
+            png::ColorType::Grayscale => buf.iter().flat_map(|&v| vec![v, v, v, 0xff]).collect(),
+            color_type => return Err(format!("unsupported color type {:?}", color_type)),
+        };
+
+        Ok(Self {
+            width: info.width,
+            height: info.height,
+            pixels,
+        })
+    }
+
+    /// Scales an area given in `TILE_SIZE` pixels to this image.
+    fn scale_area(&self, area: (u32, u32, u32, u32)) -> (u32, u32, u32, u32) {
+        let scale = |v: u32| v * self.width / TILE_SIZE;
+        (scale(area.0), scale(area.1), scale(area.2), scale(area.3))
+    }
+
+    /// Average color of the pixels `keep` picks by index, weighted by their
+    /// opacity. `None` if it picks none that can be seen.
+    fn average_color<F: Fn(usize) -> bool>(&self, keep: F) -> Option<Color> {
+        let mut sum = [0u64; 3];
+        let mut weight = 0u64;
+        for (i, pixel) in self.pixels.chunks(4).enumerate() {
+            if keep(i) {
+                let alpha = pixel[3] as u64;
+                for c in 0..3 {
+                    sum[c] += pixel[c] as u64 * alpha;
+                }
+                weight += alpha;
+            }
+        }
+
+        if weight == 0 {
+            return None;
+        }
+        let channel = |c: usize| (sum[c] / weight) as u8;
+        Some((channel(0), channel(1), channel(2)))
+    }
+
+    /// The color of whatever is drawn over `blank`, like a tile's number.
+    /// `None` if the two aren't the same size, or don't differ.
+    fn ink_color(&self, blank: &Image) -> Option<Color> {
+        if (self.width, self.height) != (blank.width, blank.height) {
+            return None;
+        }
+
+        self.average_color(|i| {
+            let pixel = &self.pixels[4 * i..4 * i + 4];
+            let under = &blank.pixels[4 * i..4 * i + 4];
+            let difference: i32 = (0..4).map(|c| (pixel[c] as i32 - under[c] as i32).abs()).sum();
+            difference >= INK_THRESHOLD
+        })
+    }
+
+    /// Makes everything outside a hexagon with points at the top and
+    /// bottom transparent. It fills the image when drawn stretched to a
+    /// regular hexagon's proportions.
+    fn cut_hexagon(&mut self) {
+        let (width, height) = (self.width as f32, self.height as f32);
+        // the slanted edges run from the points to a quarter of the way
+        // along each side
+        let (nx, ny) = (height / 4.0, width / 2.0);
+        let length = (nx * nx + ny * ny).sqrt();
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let across = (x as f32 + 0.5 - width / 2.0).abs();
+                let down = y as f32 + 0.5;
+                // distance inside the nearest slanted edge, in pixels
+                let inside = (down.min(height - down) * ny - across * nx) / length;
+                let coverage = (inside + 0.5).clamp(0.0, 1.0);
+
+                let alpha = &mut self.pixels[4 * (x + y * self.width) as usize + 3];
+                *alpha = (*alpha as f32 * coverage).round() as u8;
+            }
+        }
+    }
+
+    fn transparent(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0; 4 * width as usize * height as usize],
+        }
+    }
+
+    /// Draws `text` as large as fits in `area` (left, top, width, height),
+    /// centered, with an optional drop shadow.
+    fn draw_text(
+        &mut self,
+        text: &str,
+        area: (u32, u32, u32, u32),
+        color: [u8; 3],
+        shadow: Option<[u8; 3]>,
+    ) {
+        let (left, top, width, height) = area;
+        let text_size = (font::text_width(text) + 1, font::GLYPH_HEIGHT + 1);
+        let scale = (width / text_size.0).min(height / text_size.1).max(1);
+        let left = left + width.saturating_sub(text_size.0 * scale) / 2;
+        let top = top + height.saturating_sub(text_size.1 * scale) / 2;
+
+        let mut layers = vec![(0, color)];
+        if let Some(shadow) = shadow {
+            layers.insert(0, (1, shadow));
+        }
+
+        for (offset, color) in layers {
+            font::for_each_pixel(text, |x, y| {
+                for py in 0..scale {
+                    for px in 0..scale {
+                        self.put_pixel(
+                            left + (x + offset) * scale + px,
+                            top + (y + offset) * scale + py,
+                            color,
+                        );
+                    }
+                }
+            });
+        }
+    }
+
+    fn put_pixel(&mut self, x: u32, y: u32, color: [u8; 3]) {
+        if x < self.width && y < self.height {
+            let i = 4 * (x + y * self.width) as usize;
+            self.pixels[i..i + 3].copy_from_slice(&color);
+            self.pixels[i + 3] = 0xff;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tile_numbers(assets: &Assets) -> Vec<Image> {
+        (0..=tile_art::MAX_NUMBER)
+            .map(|n| assets.load(&format!("src/textures/{}.png", n), Image::decode))
+            .collect()
+    }
+
+    #[test]
+    fn samples_debris_colors_from_the_tiles() {
+        let colors = Textures::sample_debris_colors(&tile_numbers(&Assets::embedded()));
+
+        assert_eq!(colors.len(), tile_art::MAX_NUMBER as usize + 1);
+        // every number's ink was found
+        assert!(!colors.contains(&generated_number_color()), "{:?}", colors);
+        for n in 1..colors.len() {
+            assert!(!colors[n + 1..].contains(&colors[n]), "{} shares its color", n);
+        }
+    }
+
+    #[test]
+    fn samples_the_same_debris_colors_from_the_art_as_the_tiles() {
+        let assets = Assets::embedded();
+        let art = match TileArt::load(&assets.read("tiles.svg")) {
+            Ok(art) => art,
+            // the game uses the tiles then, which the test above covers
+            Err(e) => {
+                eprintln!("can't load the tile art, skipping: {}", e);
+                return;
+            }
+        };
+
+        let from_art = Textures::art_debris_colors(&art);
+        let from_tiles = Textures::sample_debris_colors(&tile_numbers(&assets));
+        for (n, (art, tile)) in from_art.iter().zip(&from_tiles).enumerate() {
+            let distance = (art.0 as i32 - tile.0 as i32).abs()
+                + (art.1 as i32 - tile.1 as i32).abs()
+                + (art.2 as i32 - tile.2 as i32).abs();
+            assert!(distance < 48, "{}: {:?} from the art, {:?} from the tiles", n, art, tile);
+        }
+    }
+
+    #[test]
+    fn rejects_huge_images_from_their_header() {
+        // a 65535x65535 RGBA header, with a few bytes of image data
+        let png = [
+            0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d,
+            0x49, 0x48, 0x44, 0x52, 0x00, 0x00, 0xff, 0xff, 0x00, 0x00, 0xff, 0xff,
+            0x08, 0x06, 0x00, 0x00, 0x00, 0xb6, 0x05, 0xd9, 0x50, 0x00, 0x00, 0x00,
+            0x0b, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9c, 0x63, 0x60, 0x80, 0x00, 0x00,
+            0x00, 0x08, 0x00, 0x01, 0xb7, 0x58, 0x73, 0x95, 0x00, 0x00, 0x00, 0x00,
+            0x49, 0x45, 0x4e, 0x44, 0xae, 0x42, 0x60, 0x82,
+        ];
+
+        let error = Image::decode(&png).unwrap_err();
+        assert!(error.contains("larger than"), "{}", error);
+    }
+}
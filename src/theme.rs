@@ -0,0 +1,219 @@
+//! Theme packs: a directory or zip archive holding a `theme.txt` manifest
+//! that names tile images and colors. Anything a theme leaves out, or gets
+//! wrong, falls back to the built in art.
+//!
+//! The manifest is a `config` file:
+//!
+//! ```text
+//! name = Night
+//! background = #0b1020
+//! particle = #ffd080
+//! status_bar = #1c2740
+//! unrevealed = unrevealed.png
+//! 1 = numbers/1.png
+//! ```
+//!
+//! Tiles are `unrevealed`, `hover`, `mine`, `flag`, `hover_flag` and the
+//! numbers `0` to `8`, as square PNGs of any size.
+
+use crate::config;
+use crate::textures::Image;
+use crate::tile_art::Tile;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+pub const MANIFEST: &str = "theme.txt";
+/// Where themes are looked for, relative to the working directory.
+pub const THEMES_DIR: &str = "themes";
+
+pub type Color = (u8, u8, u8);
+
+#[derive(Debug, Clone, Copy)]
+pub struct Colors {
+    pub background: Color,
+    pub particle: Color,
+    pub status_bar: Color,
+}
+
+impl Default for Colors {
+    fn default() -> Self {
+        Self {
+            background: (0xff, 0x00, 0xff),
+            particle: (0xff, 0xff, 0xff),
+            status_bar: (0xff, 0x00, 0xff),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Theme {
+    pub name: String,
+    /// Only the tiles the theme replaces.
+    pub tiles: Vec<(Tile, Image)>,
+    pub colors: Colors,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            name: "default".into(),
+            tiles: Vec::new(),
+            colors: Colors::default(),
+        }
+    }
+}
+
+impl Theme {
+    /// Loads the theme at `path`. Fails only if there's no readable
+    /// manifest; problems with single entries are returned alongside the
+    /// theme, which uses the defaults for them.
+    pub fn load(path: &Path) -> Result<(Self, Vec<String>), String> {
+        let mut pack = Pack::open(path)?;
+        let manifest = pack.read(MANIFEST)?;
+        let manifest = String::from_utf8(manifest).map_err(|_| format!("{} isn't valid UTF-8", MANIFEST))?;
+
+        let mut theme = Theme {
+            name: path
+                .file_stem()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            ..Default::default()
+        };
+        let mut problems = Vec::new();
+
+        for (line, entry) in config::parse(&manifest) {
+            let (key, value) = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    problems.push(format!("line {}: {}", line, e));
+                    continue;
+                }
+            };
+
+            let result = match key {
+                "name" => {
+                    theme.name = value.into();
+                    Ok(())
+                }
+                "background" => parse_color(value).map(|color| theme.colors.background = color),
+                "particle" => parse_color(value).map(|color| theme.colors.particle = color),
+                "status_bar" => parse_color(value).map(|color| theme.colors.status_bar = color),
+                _ => match Tile::from_name(key) {
+                    Some(tile) => pack
+                        .read(value)
+                        .and_then(|data| Image::decode(&data))
+                        .and_then(|image| {
+                            if image.width != image.height {
+                                return Err(format!("{} isn't square", value));
+                            }
+                            theme.tiles.push((tile, image));
+                            Ok(())
+                        }),
+                    None => Err(format!("unknown key {}", key)),
+                },
+            };
+
+            if let Err(e) = result {
+                problems.push(format!("line {}: {}", line, e));
+            }
+        }
+
+        Ok((theme, problems))
+    }
+}
+
+/// The theme packs in `THEMES_DIR`, sorted by path.
+pub fn discover() -> Vec<PathBuf> {
+    let entries = match std::fs::read_dir(THEMES_DIR) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut themes: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir() || path.extension().map(|ext| ext == "zip").unwrap_or(false))
+        .collect();
+    themes.sort();
+
+    themes
+}
+
+/// Parses colors like `#16233c`.
+pub fn parse_color(value: &str) -> Result<Color, String> {
+    let invalid = || format!("invalid color {}", value);
+
+    let hex = value.strip_prefix('#').ok_or_else(invalid)?;
+    if hex.len() != 6 {
+        return Err(invalid());
+    }
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2).ok_or_else(invalid)?, 16).map_err(|_| invalid());
+
+    Ok((channel(0)?, channel(2)?, channel(4)?))
+}
+
+/// Where a theme's files come from.
+enum Pack {
+    Directory(PathBuf),
+    Zip(zip::ZipArchive<File>),
+}
+
+impl Pack {
+    fn open(path: &Path) -> Result<Self, String> {
+        if path.is_dir() {
+            return Ok(Pack::Directory(path.into()));
+        }
+
+        let file = File::open(path).map_err(|e| format!("can't open {}: {}", path.display(), e))?;
+        let archive = zip::ZipArchive::new(file).map_err(|e| format!("can't read {}: {}", path.display(), e))?;
+
+        Ok(Pack::Zip(archive))
+    }
+
+    fn read(&mut self, name: &str) -> Result<Vec<u8>, String> {
+        let error = |e: &dyn std::fmt::Display| format!("can't read {}: {}", name, e);
+
+        match self {
+            Pack::Directory(dir) => std::fs::read(dir.join(name)).map_err(|e| error(&e)),
+            Pack::Zip(archive) => {
+                let mut file = archive.by_name(name).map_err(|e| error(&e))?;
+                let mut data = Vec::new();
+                file.read_to_end(&mut data).map_err(|e| error(&e))?;
+                Ok(data)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_colors() {
+        assert_eq!(parse_color("#0b1020"), Ok((0x0b, 0x10, 0x20)));
+        assert!(parse_color("0b1020").is_err());
+        assert!(parse_color("#0b10").is_err());
+        assert!(parse_color("#0b10zz").is_err());
+    }
+
+    #[test]
+    fn loads_colors_from_a_manifest() {
+        let dir = std::env::temp_dir().join(format!("sdl2-minesweeper-theme-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let manifest = "name = Night\nbackground = #0b1020 # dark\nparticle = #ffd080\nstatus_bar = #1c2740\nbogus = 1\n";
+        std::fs::write(dir.join(MANIFEST), manifest).unwrap();
+
+        let loaded = Theme::load(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+        let (theme, problems) = loaded.unwrap();
+
+        assert_eq!(theme.name, "Night");
+        assert_eq!(theme.colors.background, (0x0b, 0x10, 0x20));
+        assert_eq!(theme.colors.particle, (0xff, 0xd0, 0x80));
+        assert_eq!(theme.colors.status_bar, (0x1c, 0x27, 0x40));
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("line 5:"), "{}", problems[0]);
+    }
+}
@@ -0,0 +1,124 @@
+//! Rasterizes the tiles from `tiles.svg`, the source of the embedded PNGs,
+//! so they can be drawn crisply at any size.
+
+use crate::textures::Image;
+use resvg::{tiny_skia, usvg};
+
+/// Ids of each tile's group in `tiles.svg`.
+const UNREVEALED_ID: &str = "g1006";
+const HOVER_ID: &str = "g1006-9";
+const MINE_ID: &str = "g1017";
+const FLAG_ID: &str = "g1054-8-5-6-7-0-7";
+const HOVER_FLAG_ID: &str = "g1054-8-5-6-7-0-7-3";
+const NUMBER_IDS: [&str; 9] = [
+    "g1022",
+    "g1029",
+    "g1036",
+    "g1054",
+    "g1054-8",
+    "g1054-8-5",
+    "g1054-8-5-6",
+    "g1054-8-5-6-7",
+    "g1054-8-5-6-7-0",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Tile {
+    Unrevealed,
+    Hover,
+    Mine,
+    Flag,
+    HoverFlag,
+    Number(u8),
+}
+
+impl Tile {
+    /// Parses the names themes refer to tiles by.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "unrevealed" => Some(Tile::Unrevealed),
+            "hover" => Some(Tile::Hover),
+            "mine" => Some(Tile::Mine),
+            "flag" => Some(Tile::Flag),
+            "hover_flag" => Some(Tile::HoverFlag),
+            _ => name.parse().ok().filter(|&n| n <= MAX_NUMBER).map(Tile::Number),
+        }
+    }
+
+    fn id(self) -> &'static str {
+        match self {
+            Tile::Unrevealed => UNREVEALED_ID,
+            Tile::Hover => HOVER_ID,
+            Tile::Mine => MINE_ID,
+            Tile::Flag => FLAG_ID,
+            Tile::HoverFlag => HOVER_FLAG_ID,
+            Tile::Number(n) => NUMBER_IDS[n as usize],
+        }
+    }
+}
+
+/// The highest number drawn in `tiles.svg`.
+pub const MAX_NUMBER: u8 = 8;
+
+pub struct TileArt {
+    tree: usvg::Tree,
+    /// The tiles are squares laid out in a row, as tall as the document.
+    tile_size: f32,
+}
+
+impl TileArt {
+    /// Parses `tiles.svg`. Fails if it can't be parsed, or if there are no
+    /// fonts to draw the numbers with.
+    pub fn load(data: &[u8]) -> Result<Self, String> {
+        let mut options = usvg::Options::default();
+        options.fontdb_mut().load_system_fonts();
+        if options.fontdb.is_empty() {
+            return Err("no fonts to draw the numbers with".into());
+        }
+
+        let tree = usvg::Tree::from_data(data, &options)
+            .map_err(|e| e.to_string())?;
+        let tile_size = tree.size().height();
+
+        for id in [UNREVEALED_ID, HOVER_ID, MINE_ID, FLAG_ID, HOVER_FLAG_ID]
+            .iter()
+            .chain(NUMBER_IDS.iter())
+        {
+            if tree.node_by_id(id).is_none() {
+                return Err(format!("missing tile {}", id));
+            }
+        }
+
+        Ok(Self { tree, tile_size })
+    }
+
+    /// Draws `tile` into a `size` pixels square image.
+    pub fn rasterize(&self, tile: Tile, size: u32) -> Image {
+        let node = self.tree.node_by_id(tile.id()).unwrap();
+
+        // the filters on some tiles bleed a little past them, so find the
+        // tile's slot in the row rather than using its exact bounds
+        let bounds = node.abs_bounding_box();
+        let column = (bounds.x() / self.tile_size).round();
+
+        let scale = size as f32 / self.tile_size;
+        let transform = tiny_skia::Transform::from_scale(scale, scale)
+            .pre_translate(-column * self.tile_size, 0.0);
+
+        let mut pixmap = tiny_skia::Pixmap::new(size, size).unwrap();
+        resvg::render(&self.tree, transform, &mut pixmap.as_mut());
+
+        // SDL wants straight alpha
+        let mut pixels = Vec::with_capacity(4 * size as usize * size as usize);
+        for pixel in pixmap.pixels() {
+            let color = pixel.demultiply();
+            pixels.extend_from_slice(&[color.red(), color.green(), color.blue(), color.alpha()]);
+        }
+
+        Image {
+            width: size,
+            height: size,
+            pixels,
+        }
+    }
+}
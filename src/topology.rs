@@ -0,0 +1,86 @@
+/// How the edges of the board connect to each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Topology {
+    /// A plain rectangle: cells on the edges simply have fewer neighbors.
+    #[default]
+    Flat,
+    /// Both pairs of opposite edges are joined.
+    Torus,
+    /// The left and right edges are joined.
+    HorizontalCylinder,
+    /// The top and bottom edges are joined.
+    VerticalCylinder,
+}
+
+impl Topology {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "flat" => Some(Topology::Flat),
+            "torus" => Some(Topology::Torus),
+            "hcylinder" => Some(Topology::HorizontalCylinder),
+            "vcylinder" => Some(Topology::VerticalCylinder),
+            _ => None,
+        }
+    }
+
+    pub fn wraps_horizontally(self) -> bool {
+        matches!(self, Topology::Torus | Topology::HorizontalCylinder)
+    }
+
+    pub fn wraps_vertically(self) -> bool {
+        matches!(self, Topology::Torus | Topology::VerticalCylinder)
+    }
+
+    /// Maps coordinates that may lie outside the board back onto it.
+    /// Returns `None` if they fall off an edge that doesn't wrap.
+    pub fn wrap(self, pos: (i32, i32), size: (u16, u16)) -> Option<(u16, u16)> {
+        let wrap_axis = |v: i32, len: u16, wraps: bool| {
+            let len = len as i32;
+            if wraps {
+                Some(v.rem_euclid(len) as u16)
+            } else if v >= 0 && v < len {
+                Some(v as u16)
+            } else {
+                None
+            }
+        };
+
+        Some((
+            wrap_axis(pos.0, size.0, self.wraps_horizontally())?,
+            wrap_axis(pos.1, size.1, self.wraps_vertically())?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wraps_around_a_torus() {
+        let size = (4, 3);
+        assert_eq!(Topology::Torus.wrap((-1, -1), size), Some((3, 2)));
+        assert_eq!(Topology::Torus.wrap((4, 3), size), Some((0, 0)));
+        assert_eq!(Topology::Torus.wrap((9, -7), size), Some((1, 2)));
+        assert_eq!(Topology::Torus.wrap((2, 1), size), Some((2, 1)));
+    }
+
+    #[test]
+    fn falls_off_a_flat_board() {
+        let size = (4, 3);
+        assert_eq!(Topology::Flat.wrap((0, 0), size), Some((0, 0)));
+        assert_eq!(Topology::Flat.wrap((3, 2), size), Some((3, 2)));
+        assert_eq!(Topology::Flat.wrap((-1, 0), size), None);
+        assert_eq!(Topology::Flat.wrap((0, 3), size), None);
+        assert_eq!(Topology::Flat.wrap((4, 0), size), None);
+    }
+
+    #[test]
+    fn wraps_cylinders_along_one_axis() {
+        let size = (4, 3);
+        assert_eq!(Topology::HorizontalCylinder.wrap((-1, 1), size), Some((3, 1)));
+        assert_eq!(Topology::HorizontalCylinder.wrap((0, -1), size), None);
+        assert_eq!(Topology::VerticalCylinder.wrap((1, -1), size), Some((1, 2)));
+        assert_eq!(Topology::VerticalCylinder.wrap((-1, 0), size), None);
+    }
+}